@@ -0,0 +1,307 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+
+use crate::logger;
+
+/// Reading an alert rule watches
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMeasure {
+    /// Normalized 0-100 level
+    #[default]
+    Level,
+    /// Reading in dB (SPL when calibrated)
+    Db,
+}
+
+/// A named alert with its trigger conditions and actions, as written in config.json
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AlertRule {
+    /// Shown in alerts and used in event logs, topics and file names
+    pub name: String,
+    /// Whether the threshold applies to the 0-100 level or the dB reading
+    pub measure: AlertMeasure,
+    /// Reading above which the rule triggers
+    pub threshold: f32,
+    /// Clears once the reading is no longer above this, defaults to the threshold
+    pub release_threshold: Option<f32>,
+    /// How long the reading must stay above the threshold to trigger
+    pub min_duration_s: f32,
+    /// Quiet time after clearing before the rule can trigger again
+    pub cooldown_s: f32,
+    /// Shell command run when the alert triggers
+    pub command: Option<String>,
+    /// JSON Lines file recording each trigger and clear
+    pub event_log: Option<String>,
+    /// Ring the terminal bell when the alert triggers
+    pub bell: bool,
+}
+
+impl Default for AlertRule {
+    fn default() -> Self {
+        Self {
+            name: "alert".to_string(),
+            measure: AlertMeasure::Level,
+            threshold: 80.0,
+            release_threshold: None,
+            min_duration_s: 0.0,
+            cooldown_s: 0.0,
+            command: None,
+            event_log: None,
+            bell: false,
+        }
+    }
+}
+
+/// Change of an alert rule's state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertEvent {
+    /// The reading stayed above the threshold for the minimum duration
+    Triggered,
+    /// The reading fell back to the release threshold
+    Cleared,
+}
+
+impl AlertEvent {
+    /// Lower-case name used in event logs and topics
+    pub fn name(self) -> &'static str {
+        match self {
+            AlertEvent::Triggered => "triggered",
+            AlertEvent::Cleared => "cleared",
+        }
+    }
+}
+
+/// Readings passed to the actions of an alert event
+#[derive(Debug, Clone, Copy)]
+pub struct AlertReading {
+    /// Normalized 0-100 level
+    pub level: f32,
+    /// Reading in dB, SPL when calibrated
+    pub db: f32,
+    /// Audio time since metering started
+    pub elapsed_s: f64,
+}
+
+// One line of an alert's event log
+#[derive(Serialize)]
+struct EventRecord<'a> {
+    timestamp: String,
+    rule: &'a str,
+    event: &'static str,
+    level: f32,
+    db: f32,
+    threshold: f32,
+    elapsed_s: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_s: Option<f64>, // How long the alert lasted, on clearing
+}
+
+/// Tracks one rule over time. Durations come from the audio itself, so files
+/// analyzed faster than real time behave like live input
+#[derive(Debug, Clone)]
+pub struct AlertMonitor {
+    rule: AlertRule,
+    active: bool,
+    above_s: f64,    // How long the reading has been above the threshold
+    active_s: f64,   // How long the current alert has lasted
+    cooldown_s: f64, // Cooldown left before the next trigger
+    triggered: usize,
+}
+
+impl AlertMonitor {
+    /// Monitor for `rule`, starting out clear
+    pub fn new(rule: AlertRule) -> Self {
+        Self { rule, active: false, above_s: 0.0, active_s: 0.0, cooldown_s: 0.0, triggered: 0 }
+    }
+
+    /// The rule being monitored
+    pub fn rule(&self) -> &AlertRule {
+        &self.rule
+    }
+
+    /// Whether the rule has triggered and not cleared since
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Times the rule has triggered
+    pub fn triggered(&self) -> usize {
+        self.triggered
+    }
+
+    /// Forget the current state and count as if metering had just started
+    pub fn reset(&mut self) {
+        *self = Self::new(self.rule.clone());
+    }
+
+    /// Feed the rule's reading for a block lasting `duration_s`; returns the event
+    /// the block caused, if any
+    pub fn update(&mut self, value: f32, duration_s: f64) -> Option<AlertEvent> {
+        if self.active {
+            self.active_s += duration_s;
+            let release = self.rule.release_threshold.unwrap_or(self.rule.threshold);
+            if value <= release || value.is_nan() {
+                self.active = false;
+                self.above_s = 0.0;
+                self.cooldown_s = self.rule.cooldown_s as f64;
+                return Some(AlertEvent::Cleared);
+            }
+            return None;
+        }
+
+        self.cooldown_s = (self.cooldown_s - duration_s).max(0.0);
+        if value > self.rule.threshold {
+            self.above_s += duration_s;
+        } else {
+            self.above_s = 0.0;
+        }
+        // Small tolerance so block durations adding up to the minimum count as reaching it
+        if self.above_s > 0.0 && self.above_s >= self.rule.min_duration_s as f64 - 1e-6 && self.cooldown_s <= 1e-6 {
+            self.active = true;
+            self.active_s = 0.0;
+            self.triggered += 1;
+            return Some(AlertEvent::Triggered);
+        }
+        None
+    }
+
+    /// Carry out the rule's actions for `event`
+    pub fn run_actions(&self, event: AlertEvent, reading: &AlertReading) -> io::Result<()> {
+        if let Some(path) = &self.rule.event_log {
+            self.log_event(path, event, reading)?;
+        }
+        if event == AlertEvent::Triggered {
+            if self.rule.bell {
+                print!("\x07");
+                io::stdout().flush()?;
+            }
+            if let Some(mut child) = self.spawn_command(reading)? {
+                // Reap the command without holding up metering
+                thread::spawn(move || child.wait());
+            }
+        }
+        Ok(())
+    }
+
+    /// The event as one JSON object, as written to the event log
+    pub fn event_json(&self, event: AlertEvent, reading: &AlertReading) -> io::Result<String> {
+        let record = EventRecord {
+            timestamp: logger::timestamp(SystemTime::now()),
+            rule: &self.rule.name,
+            event: event.name(),
+            level: reading.level,
+            db: reading.db,
+            threshold: self.rule.threshold,
+            elapsed_s: reading.elapsed_s,
+            duration_s: (event == AlertEvent::Cleared).then_some(self.active_s),
+        };
+        serde_json::to_string(&record).map_err(io::Error::other)
+    }
+
+    fn log_event(&self, path: &str, event: AlertEvent, reading: &AlertReading) -> io::Result<()> {
+        let line = self.event_json(event, reading)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", line)
+    }
+
+    // Start the rule's command through the shell with the readings in its environment
+    fn spawn_command(&self, reading: &AlertReading) -> io::Result<Option<Child>> {
+        let Some(command) = &self.rule.command else {
+            return Ok(None);
+        };
+        Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("DB_METER_ALERT", &self.rule.name)
+            .env("DB_METER_LEVEL", format!("{:.2}", reading.level))
+            .env("DB_METER_DB", format!("{:.2}", reading.db))
+            .env("DB_METER_THRESHOLD", format!("{:.2}", self.rule.threshold))
+            .env("DB_METER_ELAPSED_S", format!("{:.3}", reading.elapsed_s))
+            .env("DB_METER_TIMESTAMP", logger::timestamp(SystemTime::now()))
+            .spawn()
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn rule() -> AlertRule {
+        AlertRule { name: "loud".to_string(), threshold: 80.0, ..AlertRule::default() }
+    }
+
+    #[test]
+    fn triggers_after_minimum_duration() {
+        let mut monitor = AlertMonitor::new(AlertRule { min_duration_s: 0.3, ..rule() });
+        assert_eq!(monitor.update(90.0, 0.1), None);
+        assert_eq!(monitor.update(90.0, 0.1), None);
+        // A dip restarts the count
+        assert_eq!(monitor.update(50.0, 0.1), None);
+        assert_eq!(monitor.update(90.0, 0.1), None);
+        assert_eq!(monitor.update(90.0, 0.1), None);
+        assert_eq!(monitor.update(90.0, 0.1), Some(AlertEvent::Triggered));
+        assert!(monitor.active());
+        assert_eq!(monitor.triggered(), 1);
+    }
+
+    #[test]
+    fn release_threshold_adds_hysteresis() {
+        let mut monitor = AlertMonitor::new(AlertRule { release_threshold: Some(70.0), ..rule() });
+        assert_eq!(monitor.update(85.0, 0.1), Some(AlertEvent::Triggered));
+        assert_eq!(monitor.update(75.0, 0.1), None);
+        assert!(monitor.active());
+        assert_eq!(monitor.update(70.0, 0.1), Some(AlertEvent::Cleared));
+        assert!(!monitor.active());
+    }
+
+    #[test]
+    fn cooldown_suppresses_retriggering() {
+        let mut monitor = AlertMonitor::new(AlertRule { cooldown_s: 1.0, ..rule() });
+        assert_eq!(monitor.update(90.0, 0.5), Some(AlertEvent::Triggered));
+        assert_eq!(monitor.update(10.0, 0.5), Some(AlertEvent::Cleared));
+        assert_eq!(monitor.update(90.0, 0.5), None);
+        assert_eq!(monitor.update(90.0, 0.5), Some(AlertEvent::Triggered));
+        assert_eq!(monitor.triggered(), 2);
+    }
+
+    #[test]
+    fn actions_write_events_and_run_commands() {
+        let dir = std::env::temp_dir().join(format!("db_meter_alert_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let events = dir.join("events.jsonl");
+        let output = dir.join("command.txt");
+        let mut monitor = AlertMonitor::new(AlertRule {
+            event_log: Some(events.to_string_lossy().into_owned()),
+            command: Some(format!("echo \"$DB_METER_ALERT $DB_METER_LEVEL\" > '{}'", output.display())),
+            ..rule()
+        });
+        let reading = AlertReading { level: 91.5, db: -8.5, elapsed_s: 1.0 };
+
+        assert_eq!(monitor.update(91.5, 0.1), Some(AlertEvent::Triggered));
+        monitor.spawn_command(&reading).unwrap().unwrap().wait().unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "loud 91.50\n");
+
+        monitor.log_event(&events.to_string_lossy(), AlertEvent::Triggered, &reading).unwrap();
+        monitor.update(10.0, 0.4);
+        monitor.log_event(&events.to_string_lossy(), AlertEvent::Cleared, &reading).unwrap();
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&events)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "triggered");
+        assert_eq!(lines[1]["event"], "cleared");
+        assert!((lines[1]["duration_s"].as_f64().unwrap() - 0.4).abs() < 1e-9);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use std::time::Instant;
+
+// Readings below this are treated as silence so the dynamics stay finite
+const FLOOR_DB: f32 = -200.0;
+
+/// Dynamics shared by every bar on the display
+#[derive(Debug, Clone, Copy)]
+pub struct BallisticsSettings {
+    /// Time constant of a rising bar, 0 for instant
+    pub attack_s: f32,
+    /// Time constant of a falling bar, 0 for instant
+    pub release_s: f32,
+    /// How long the peak marker stays put
+    pub hold_s: f32,
+    /// Fall rate of the peak marker after the hold, 0 to drop at once
+    pub decay_db_per_s: f32,
+}
+
+/// Display dynamics of one bar: the bar follows the reading with attack and
+/// release time constants while a peak marker holds the highest reading and then
+/// decays, all in dB and driven by the time between updates
+#[derive(Debug, Clone)]
+pub struct BarBallistics {
+    settings: BallisticsSettings,
+    bar_db: f32,
+    peak_db: f32,
+    held_s: f32,
+    last: Option<Instant>,
+}
+
+impl BarBallistics {
+    /// Bar and peak marker starting out at silence
+    pub fn new(settings: BallisticsSettings) -> Self {
+        Self { settings, bar_db: FLOOR_DB, peak_db: FLOOR_DB, held_s: 0.0, last: None }
+    }
+
+    /// Feed the latest reading and return the (bar, peak marker) levels in dB
+    pub fn update(&mut self, db: f32, now: Instant) -> (f32, f32) {
+        let elapsed = match self.last {
+            Some(last) => now.duration_since(last).as_secs_f32(),
+            None => {
+                self.bar_db = db.max(FLOOR_DB);
+                0.0
+            }
+        };
+        self.last = Some(now);
+        self.step(db, elapsed)
+    }
+
+    /// Feed a reading that covers `elapsed_s` of audio, for dynamics that follow
+    /// the audio rather than the wall clock, and return the (bar, peak marker) levels
+    pub fn advance(&mut self, db: f32, elapsed_s: f32) -> (f32, f32) {
+        self.step(db, elapsed_s)
+    }
+
+    /// Current (bar, peak marker) levels in dB
+    pub fn levels(&self) -> (f32, f32) {
+        (self.bar_db, self.peak_db)
+    }
+
+    fn step(&mut self, db: f32, elapsed_s: f32) -> (f32, f32) {
+        let db = if db.is_nan() { FLOOR_DB } else { db.max(FLOOR_DB) };
+
+        let tau = if db > self.bar_db { self.settings.attack_s } else { self.settings.release_s };
+        if tau <= 0.0 {
+            self.bar_db = db;
+        } else {
+            self.bar_db += (db - self.bar_db) * (1.0 - (-elapsed_s / tau).exp());
+        }
+
+        if db >= self.peak_db {
+            self.peak_db = db;
+            self.held_s = 0.0;
+        } else {
+            let previously_held = self.held_s;
+            self.held_s += elapsed_s;
+            if self.held_s > self.settings.hold_s {
+                if self.settings.decay_db_per_s <= 0.0 {
+                    self.peak_db = db;
+                } else {
+                    // Only the part of this step past the hold time counts towards the decay
+                    let decaying = self.held_s - previously_held.max(self.settings.hold_s);
+                    self.peak_db = (self.peak_db - self.settings.decay_db_per_s * decaying).max(db);
+                }
+            }
+        }
+
+        (self.bar_db, self.peak_db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: BallisticsSettings = BallisticsSettings {
+        attack_s: 0.0,
+        release_s: 0.3,
+        hold_s: 2.0,
+        decay_db_per_s: 20.0,
+    };
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() <= tolerance, "expected {} +/- {}, got {}", expected, tolerance, actual);
+    }
+
+    #[test]
+    fn bar_releases_with_time_constant() {
+        let mut bar = BarBallistics::new(SETTINGS);
+        bar.step(0.0, 0.0);
+        // After one time constant the bar has covered 63% of the step
+        let (bar_db, _) = bar.step(-30.0, 0.3);
+        assert_close(bar_db, -30.0 * (1.0 - (-1.0f32).exp()), 1e-3);
+    }
+
+    #[test]
+    fn result_depends_on_elapsed_time_not_update_count() {
+        let mut coarse = BarBallistics::new(SETTINGS);
+        let mut fine = BarBallistics::new(SETTINGS);
+        coarse.step(0.0, 0.0);
+        fine.step(0.0, 0.0);
+        let once = coarse.step(-40.0, 3.0);
+        let mut many = (0.0, 0.0);
+        for _ in 0..300 {
+            many = fine.step(-40.0, 0.01);
+        }
+        assert_close(once.0, many.0, 1e-2);
+        assert_close(once.1, many.1, 1e-2);
+    }
+
+    #[test]
+    fn peak_holds_then_decays() {
+        let mut bar = BarBallistics::new(SETTINGS);
+        bar.step(-6.0, 0.0);
+        assert_eq!(bar.step(-40.0, 1.5).1, -6.0);
+        // 0.5 s into the hold plus 0.5 s of decay at 20 dB/s
+        assert_close(bar.step(-40.0, 1.0).1, -16.0, 1e-3);
+        // Never falls below the reading
+        assert_eq!(bar.step(-40.0, 10.0).1, -40.0);
+        // A new maximum restarts the hold
+        assert_eq!(bar.step(-3.0, 0.1).1, -3.0);
+    }
+
+    #[test]
+    fn silence_stays_finite() {
+        let mut bar = BarBallistics::new(SETTINGS);
+        let (bar_db, peak_db) = bar.update(f32::NEG_INFINITY, Instant::now());
+        assert!(bar_db.is_finite() && peak_db.is_finite());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use serde::Serialize;
+
+use crate::alert::{AlertMeasure, AlertReading, AlertRule};
+use crate::logger;
+use crate::wav::{WavFormat, WavSpec, WavWriter};
+
+/// Where clips go and how much audio around the trigger they hold
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    /// Created at startup if missing
+    pub directory: PathBuf,
+    /// Audio kept from before the alert triggered, in seconds
+    pub pre_s: f32,
+    /// Audio recorded after it triggered, in seconds
+    pub post_s: f32,
+}
+
+/// Measured levels of one block inside a clip
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipLevel {
+    /// Audio time at the end of the block
+    pub elapsed_s: f64,
+    /// Main reading in dB
+    pub db: f32,
+    /// Normalized 0-100 level
+    pub level: f32,
+    /// Reading of each channel in dB
+    pub channels_db: Vec<f32>,
+}
+
+// Sidecar JSON written next to each clip
+#[derive(Serialize)]
+struct ClipInfo<'a> {
+    rule: &'a str,
+    timestamp: &'a str,
+    measure: AlertMeasure,
+    threshold: f32,
+    level: f32,
+    db: f32,
+    elapsed_s: f64,
+    start_s: f64,
+    duration_s: f64,
+    sample_rate: u32,
+    channels: usize,
+    wav: String,
+    levels: &'a [ClipLevel],
+}
+
+// A clip still collecting its post-roll
+struct PendingClip {
+    rule: AlertRule,
+    timestamp: String,
+    reading: AlertReading,
+    start_s: f64,
+    samples: Vec<f32>,
+    levels: Vec<ClipLevel>,
+    remaining_frames: usize,
+}
+
+/// Keeps the last `pre_s` seconds of raw samples and, when an alert triggers,
+/// writes them together with the following `post_s` seconds to a float WAV clip
+/// plus a JSON file of the block levels it covers
+pub struct EventCapture {
+    settings: CaptureSettings,
+    sample_rate: u32,
+    channels: usize,
+    pre_roll: VecDeque<f32>,
+    pre_levels: VecDeque<ClipLevel>,
+    pending: Vec<PendingClip>,
+}
+
+impl EventCapture {
+    /// Create the clip directory up front so a bad path shows up at startup
+    pub fn new(settings: CaptureSettings) -> io::Result<Self> {
+        fs::create_dir_all(&settings.directory)?;
+        Ok(Self {
+            settings,
+            sample_rate: 48_000,
+            channels: 1,
+            pre_roll: VecDeque::new(),
+            pre_levels: VecDeque::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Adopt a source's sample layout, dropping audio buffered in any other layout
+    pub fn set_format(&mut self, sample_rate: u32, channels: usize) {
+        if (sample_rate, channels) != (self.sample_rate, self.channels) {
+            self.sample_rate = sample_rate;
+            self.channels = channels.max(1);
+            self.pre_roll.clear();
+            self.pre_levels.clear();
+            self.pending.clear();
+        }
+    }
+
+    fn pre_roll_samples(&self) -> usize {
+        (self.settings.pre_s.max(0.0) * self.sample_rate as f32) as usize * self.channels
+    }
+
+    /// Add a block of interleaved samples and its levels, writing every clip
+    /// whose post-roll is now complete
+    pub fn push(&mut self, samples: &[f32], level: ClipLevel) -> io::Result<()> {
+        for clip in &mut self.pending {
+            let take = samples.len().min(clip.remaining_frames * self.channels);
+            clip.samples.extend_from_slice(&samples[..take]);
+            clip.levels.push(level.clone());
+            clip.remaining_frames -= take / self.channels;
+        }
+        let (finished, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|clip| clip.remaining_frames == 0);
+        self.pending = pending;
+        for clip in finished {
+            self.write_clip(&clip)?;
+        }
+
+        let capacity = self.pre_roll_samples();
+        self.pre_roll.extend(samples);
+        let excess = self.pre_roll.len().saturating_sub(capacity);
+        self.pre_roll.drain(..excess);
+
+        // Tolerance so a block ending exactly at the pre-roll boundary drops out despite rounding
+        let oldest_s = level.elapsed_s - self.settings.pre_s as f64 + 1e-6;
+        self.pre_levels.push_back(level);
+        while self.pre_levels.front().is_some_and(|level| level.elapsed_s <= oldest_s) {
+            self.pre_levels.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Start a clip for an alert that just triggered on the latest block
+    pub fn trigger(&mut self, rule: &AlertRule, reading: &AlertReading) -> io::Result<()> {
+        let frames = self.pre_roll.len() / self.channels;
+        let clip = PendingClip {
+            rule: rule.clone(),
+            timestamp: logger::timestamp(SystemTime::now()),
+            reading: *reading,
+            start_s: reading.elapsed_s - frames as f64 / self.sample_rate as f64,
+            samples: self.pre_roll.iter().copied().collect(),
+            levels: self.pre_levels.iter().cloned().collect(),
+            remaining_frames: (self.settings.post_s.max(0.0) * self.sample_rate as f32) as usize,
+        };
+        if clip.remaining_frames == 0 {
+            self.write_clip(&clip)
+        } else {
+            self.pending.push(clip);
+            Ok(())
+        }
+    }
+
+    /// Write the clips still waiting for post-roll with whatever they have, for
+    /// when the source ends
+    pub fn finish(&mut self) -> io::Result<()> {
+        for clip in std::mem::take(&mut self.pending) {
+            self.write_clip(&clip)?;
+        }
+        Ok(())
+    }
+
+    fn write_clip(&self, clip: &PendingClip) -> io::Result<()> {
+        // "loud-20240501T123000.250Z.wav": rule name and wall-clock time of the trigger
+        let rule_name: String = clip
+            .rule
+            .name
+            .chars()
+            .map(|ch| if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' { ch } else { '_' })
+            .collect();
+        let stem = format!("{}-{}", rule_name, clip.timestamp.replace([':', '-'], ""));
+        let wav_path = self.settings.directory.join(format!("{}.wav", stem));
+
+        let spec = WavSpec {
+            channels: self.channels as u16,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            format: WavFormat::Float,
+        };
+        let mut writer = WavWriter::create(&wav_path, spec)?;
+        writer.write_samples(&clip.samples)?;
+        writer.finalize()?;
+
+        let info = ClipInfo {
+            rule: &clip.rule.name,
+            timestamp: &clip.timestamp,
+            measure: clip.rule.measure,
+            threshold: clip.rule.threshold,
+            level: clip.reading.level,
+            db: clip.reading.db,
+            elapsed_s: clip.reading.elapsed_s,
+            start_s: clip.start_s,
+            duration_s: (clip.samples.len() / self.channels) as f64 / self.sample_rate as f64,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            wav: format!("{}.wav", stem),
+            levels: &clip.levels,
+        };
+        let json = serde_json::to_string_pretty(&info).map_err(io::Error::other)?;
+        fs::write(self.settings.directory.join(format!("{}.json", stem)), json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav::WavReader;
+
+    fn level(elapsed_s: f64) -> ClipLevel {
+        ClipLevel { elapsed_s, db: -20.0, level: 80.0, channels_db: vec![-20.0, -20.0] }
+    }
+
+    #[test]
+    fn clip_holds_pre_and_post_roll() {
+        let directory = std::env::temp_dir().join(format!("db_meter_capture_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let settings = CaptureSettings { directory: directory.clone(), pre_s: 0.2, post_s: 0.1 };
+        let mut capture = EventCapture::new(settings).unwrap();
+        capture.set_format(100, 2);
+
+        // Blocks of 5 frames (0.05 s) whose samples count the frames
+        let mut frame = 0;
+        let mut block = |capture: &mut EventCapture| {
+            let samples: Vec<f32> = (frame..frame + 5).flat_map(|n| [n as f32, -(n as f32)]).collect();
+            frame += 5;
+            capture.push(&samples, level(frame as f64 / 100.0)).unwrap();
+        };
+        for _ in 0..10 {
+            block(&mut capture);
+        }
+        let rule = AlertRule { name: "loud/noise".to_string(), ..AlertRule::default() };
+        capture.trigger(&rule, &AlertReading { level: 85.0, db: -15.0, elapsed_s: 0.5 }).unwrap();
+        block(&mut capture);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+        block(&mut capture);
+
+        let mut names: Vec<String> = fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 2, "{:?}", names);
+        assert!(names[0].starts_with("loud_noise-") && names[0].ends_with(".json"));
+
+        let mut reader = WavReader::open(&directory.join(&names[1])).unwrap();
+        let mut samples = Vec::new();
+        assert_eq!(reader.read_frames(100, &mut samples).unwrap(), 30);
+        // 20 frames of pre-roll ending with the triggering block, then 10 after it
+        assert_eq!(samples[0], 30.0);
+        assert_eq!(samples[samples.len() - 2], 59.0);
+
+        let info: serde_json::Value = serde_json::from_str(&fs::read_to_string(directory.join(&names[0])).unwrap()).unwrap();
+        assert_eq!(info["rule"], "loud/noise");
+        assert!((info["start_s"].as_f64().unwrap() - 0.3).abs() < 1e-9);
+        assert_eq!(info["levels"].as_array().unwrap().len(), 6);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
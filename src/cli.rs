@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+
+use db_meter::config::Config;
+use db_meter::time_weighting::TimeWeighting;
+
+pub const USAGE: &str = "\
+Usage: db_meter [options] [command]
+
+Commands:
+  monitor                  Meter the configured source live (default)
+  analyze <file.wav>       Meter a WAV file as fast as it can be read
+  list-devices             List audio hosts and their input devices
+  calibrate [dB SPL]       Measure a calibrator tone and save the offset
+
+Options:
+  --config <path>          Config file, created with defaults if missing [DB_METER_CONFIG]
+  --width <chars>          Width of the meter bar [DB_METER_WIDTH]
+  --threshold <value>      Alert threshold, in dB when the config alerts on dB [DB_METER_THRESHOLD]
+  --smoothing <weighting>  Time weighting: none, fast, slow or impulse [DB_METER_SMOOTHING]
+  --duration <seconds>     Stop after this many seconds of audio [DB_METER_DURATION]
+  -h, --help               Show this help
+
+Settings are taken from the defaults, then the config file, then the
+environment, then the options above.
+
+Exit status: 0 when done without alerts, 1 on errors, 2 on invalid usage,
+3 when an alert triggered.";
+
+// What the binary was asked to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Monitor,
+    Analyze(PathBuf),
+    ListDevices,
+    Calibrate(Option<f32>), // Reference level, the configured one when not given
+    Help,
+}
+
+// Settings that override the config file, from the environment or the command line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overrides {
+    pub width: Option<usize>,
+    pub threshold: Option<f32>,
+    pub smoothing: Option<TimeWeighting>,
+    pub duration_s: Option<f64>,
+}
+
+impl Overrides {
+    // Read the DB_METER_* variables through `var`, so tests need not touch the
+    // process environment
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let mut overrides = Self::default();
+        for (name, option) in [
+            ("DB_METER_WIDTH", "--width"),
+            ("DB_METER_THRESHOLD", "--threshold"),
+            ("DB_METER_SMOOTHING", "--smoothing"),
+            ("DB_METER_DURATION", "--duration"),
+        ] {
+            if let Some(value) = var(name) {
+                overrides.set(option, &value).map_err(|err| format!("{}: {}", name, err))?;
+            }
+        }
+        Ok(overrides)
+    }
+
+    // Set the setting behind `option`; false when it is not one of them
+    fn set(&mut self, option: &str, value: &str) -> Result<bool, String> {
+        match option {
+            "--width" => {
+                let width: usize = parse_value(value, "a width in characters")?;
+                if width == 0 {
+                    return Err(format!("invalid width '{}', expected at least 1 character", value));
+                }
+                self.width = Some(width);
+            }
+            "--threshold" => self.threshold = Some(parse_value(value, "a number")?),
+            "--smoothing" => {
+                let weighting = serde_json::from_value(serde_json::Value::String(value.to_string()))
+                    .map_err(|_| format!("invalid smoothing '{}', expected none, fast, slow or impulse", value))?;
+                self.smoothing = Some(weighting);
+            }
+            "--duration" => {
+                let duration_s: f64 = parse_value(value, "a number of seconds")?;
+                if duration_s <= 0.0 {
+                    return Err(format!("invalid duration '{}', expected a positive number of seconds", value));
+                }
+                self.duration_s = Some(duration_s);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Settings given here replace those of `self`
+    pub fn merge(self, later: Overrides) -> Overrides {
+        Overrides {
+            width: later.width.or(self.width),
+            threshold: later.threshold.or(self.threshold),
+            smoothing: later.smoothing.or(self.smoothing),
+            duration_s: later.duration_s.or(self.duration_s),
+        }
+    }
+
+    // Fails when a threshold is given but the config's alert rules carry their own
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        if let Some(width) = self.width {
+            config.meter_width = width;
+        }
+        // The threshold follows whichever measure the config alerts on
+        if let Some(threshold) = self.threshold {
+            if !config.alert_rules.is_empty() {
+                return Err("--threshold does not apply to alert_rules, set their thresholds in the config".to_string());
+            }
+            match config.alert_threshold_db.as_mut() {
+                Some(threshold_db) => *threshold_db = threshold,
+                None => config.alert_threshold = threshold,
+            }
+        }
+        if let Some(smoothing) = self.smoothing {
+            config.time_weighting = smoothing;
+        }
+        if let Some(duration_s) = self.duration_s {
+            config.duration_s = Some(duration_s);
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str, expected: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value '{}', expected {}", value, expected))
+}
+
+// Parsed command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub config_path: Option<PathBuf>,
+    pub overrides: Overrides,
+}
+
+// Parse the arguments after the program name. Options may come before or after
+// the command, as "--name value" or "--name=value"
+pub fn parse(args: &[String]) -> Result<Cli, String> {
+    let mut config_path = None;
+    let mut overrides = Overrides::default();
+    let mut positional = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Cli { command: Command::Help, config_path, overrides });
+        }
+        // Still accepted from before there were subcommands
+        if arg == "--list-devices" {
+            positional.push("list-devices".to_string());
+            continue;
+        }
+        if !arg.starts_with("--") {
+            positional.push(arg.clone());
+            continue;
+        }
+
+        let (option, inline) = match arg.split_once('=') {
+            Some((option, value)) => (option, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let value = match inline.or_else(|| args.next().cloned()) {
+            Some(value) => value,
+            None if matches!(option, "--config" | "--width" | "--threshold" | "--smoothing" | "--duration") => {
+                return Err(format!("{} needs a value", option));
+            }
+            None => return Err(format!("unknown option {}", option)),
+        };
+        if option == "--config" {
+            config_path = Some(PathBuf::from(value));
+        } else if !overrides.set(option, &value).map_err(|err| format!("{}: {}", option, err))? {
+            return Err(format!("unknown option {}", option));
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        None | Some("monitor") => Command::Monitor,
+        Some("analyze") => match positional.next() {
+            Some(path) => Command::Analyze(PathBuf::from(path)),
+            None => return Err("analyze needs a WAV file".to_string()),
+        },
+        Some("list-devices") => Command::ListDevices,
+        Some("calibrate") => match positional.next() {
+            Some(value) => Command::Calibrate(Some(parse_value(&value, "a reference level in dB SPL")?)),
+            None => Command::Calibrate(None),
+        },
+        Some(other) => return Err(format!("unknown command '{}'", other)),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument '{}'", extra));
+    }
+    Ok(Cli { command, config_path, overrides })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db_meter::alert::AlertRule;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_commands_and_options() {
+        let cli = parse(&args("--config hall.json analyze take.wav --width=60 --smoothing slow")).unwrap();
+        assert_eq!(cli.command, Command::Analyze(PathBuf::from("take.wav")));
+        assert_eq!(cli.config_path, Some(PathBuf::from("hall.json")));
+        assert_eq!(cli.overrides.width, Some(60));
+        assert_eq!(cli.overrides.smoothing, Some(TimeWeighting::Slow));
+
+        assert_eq!(parse(&[]).unwrap().command, Command::Monitor);
+        assert_eq!(parse(&args("--list-devices")).unwrap().command, Command::ListDevices);
+        assert_eq!(parse(&args("calibrate 114")).unwrap().command, Command::Calibrate(Some(114.0)));
+        assert_eq!(parse(&args("monitor --bogus -h")).unwrap_err(), "unknown option --bogus");
+        assert_eq!(parse(&args("-h --bogus")).unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        for line in ["analyze", "record", "monitor extra", "--width", "--width wide", "--width 0", "--duration 0", "--smoothing soft"] {
+            assert!(parse(&args(line)).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn flags_override_environment_override_file() {
+        let env = Overrides::from_env(|name| match name {
+            "DB_METER_WIDTH" => Some("40".to_string()),
+            "DB_METER_THRESHOLD" => Some("-12.5".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        let flags = parse(&args("--width 80 --duration 30")).unwrap().overrides;
+
+        let mut config = Config { meter_width: 20, alert_threshold_db: Some(-20.0), ..Config::default() };
+        env.clone().merge(flags).apply(&mut config).unwrap();
+        assert_eq!(config.meter_width, 80);
+        assert_eq!(config.alert_threshold_db, Some(-12.5));
+        assert_eq!(config.alert_threshold, Config::default().alert_threshold);
+        assert_eq!(config.duration_s, Some(30.0));
+        assert_eq!(config.time_weighting, Config::default().time_weighting);
+
+        let mut with_rules = Config { alert_rules: vec![AlertRule::default()], ..Config::default() };
+        assert!(env.apply(&mut with_rules).unwrap_err().starts_with("--threshold"));
+
+        let err = Overrides::from_env(|name| (name == "DB_METER_SMOOTHING").then(|| "x".to_string())).unwrap_err();
+        assert!(err.starts_with("DB_METER_SMOOTHING: invalid smoothing"), "{}", err);
+    }
+}
@@ -0,0 +1,313 @@
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::alert::{AlertMeasure, AlertMonitor, AlertRule};
+use crate::logger::LogFormat;
+use crate::meter::MonoMode;
+use crate::mqtt::MqttSettings;
+use crate::osc::OscSettings;
+use crate::processor::MeterQuantity;
+use crate::record::RecordFormat;
+use crate::scale::{DisplayScale, MeterScale};
+use crate::source::SourceConfig;
+use crate::time_weighting::TimeWeighting;
+use crate::weighting::Weighting;
+
+/// Everything configurable, as stored in config.json. Fields a file leaves out
+/// keep their defaults
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Width of the meter bar in characters
+    pub meter_width: usize,
+    /// Level log file, no logging when unset
+    pub log_path: Option<String>,
+    /// CSV or JSON Lines
+    pub log_format: LogFormat,
+    /// Time between log records, 0 for every block
+    pub log_interval_ms: u32,
+    /// Start a new log file once it reaches this size
+    pub log_rotate_bytes: Option<u64>,
+    /// Start a new log file after this many seconds
+    pub log_rotate_s: Option<u64>,
+    /// Line display or full-screen dashboard
+    pub display_mode: DisplayMode,
+    /// Redraw rate of the live display
+    pub refresh_hz: f32,
+    /// How dBFS readings map onto the bar
+    pub display_scale: DisplayScale,
+    /// Bottom of the linear scale in dBFS
+    pub scale_min_db: f32,
+    /// Top of the linear scale in dBFS
+    pub scale_max_db: f32,
+    /// Attack of the bar ballistics in ms, 0 follows the reading instantly
+    pub bar_attack_ms: f32,
+    /// Release of the bar ballistics in ms, 0 follows the reading instantly
+    pub bar_release_ms: f32,
+    /// How long the peak marker holds, in seconds
+    pub peak_hold_s: f32,
+    /// Fall rate of the peak marker after the hold, 0 drops it at once
+    pub peak_decay_db_per_s: f32,
+    /// Normalized 0-100 level that triggers the plain threshold alert
+    pub alert_threshold: f32,
+    /// Exponential time weighting (smoothing) of the RMS
+    pub time_weighting: TimeWeighting,
+    /// How a combined reading is derived from several channels
+    pub mono_mode: MonoMode,
+    /// Quantity driving the main bar, min/max and alerts
+    pub meter_quantity: MeterQuantity,
+    /// Frequency weighting applied before the RMS
+    pub weighting: Weighting,
+    /// Length of a metering block in ms
+    pub block_ms: u32,
+    /// Stop metering after this many seconds of audio, run until stopped when unset
+    pub duration_s: Option<f64>,
+    /// Where samples come from
+    pub source: SourceConfig,
+    /// Audio host for device input, the default host when unset
+    pub host: Option<String>,
+    /// Input device by name, index or unique substring, the default device when unset
+    pub device: Option<String>,
+    /// Added to dBFS readings to give dB SPL
+    pub calibration_offset_db: f32,
+    /// Level of the calibrator tone in dB SPL
+    pub calibration_reference_db: f32,
+    /// Alert on the reading in dB (SPL when calibrated) instead of the 0-100 level
+    pub alert_threshold_db: Option<f32>,
+    /// Named alerts; when empty, alert_threshold(_db) acts as a single rule
+    pub alert_rules: Vec<AlertRule>,
+    /// Where to save a WAV clip of each alert, no capture when unset
+    pub capture_directory: Option<String>,
+    /// Audio kept from before the alert triggered
+    pub capture_pre_s: f32,
+    /// Audio recorded after it triggered
+    pub capture_post_s: f32,
+    /// Record the input to this WAV file while metering live
+    pub record_path: Option<String>,
+    /// Sample encoding of the recording
+    pub record_format: RecordFormat,
+    /// Split the recording into numbered files of this length
+    pub record_segment_s: Option<f64>,
+    /// Address serving Prometheus metrics on /metrics, e.g. "127.0.0.1:9464"
+    pub metrics_listen: Option<String>,
+    /// Address serving live readings to browsers, e.g. "127.0.0.1:8080"
+    pub live_listen: Option<String>,
+    /// Readings sent to each browser per second
+    pub live_rate_hz: f32,
+    /// Publish level summaries and alert events to an MQTT broker
+    pub mqtt: Option<MqttSettings>,
+    /// Send levels, peaks and alerts as Open Sound Control over UDP
+    pub osc: Option<OscSettings>,
+    /// Rolling windows for Leq and exceedance levels, next to the whole session
+    pub statistics_windows_s: Vec<f64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            meter_width: 100,
+            log_path: None,
+            log_format: LogFormat::Csv,
+            log_interval_ms: 1000,
+            log_rotate_bytes: None,
+            log_rotate_s: None,
+            display_mode: DisplayMode::Line,
+            refresh_hz: 20.0,
+            display_scale: DisplayScale::Linear,
+            scale_min_db: -100.0,
+            scale_max_db: 0.0,
+            bar_attack_ms: 10.0,
+            bar_release_ms: 300.0,
+            peak_hold_s: 2.0,
+            peak_decay_db_per_s: 20.0,
+            alert_threshold: 80.0,
+            time_weighting: TimeWeighting::Fast,
+            mono_mode: MonoMode::Off,
+            meter_quantity: MeterQuantity::Rms,
+            weighting: Weighting::Z,
+            block_ms: 100,
+            duration_s: None,
+            source: SourceConfig::Device,
+            host: None,
+            device: None,
+            calibration_offset_db: 0.0,
+            calibration_reference_db: 94.0,
+            alert_threshold_db: None,
+            alert_rules: Vec::new(),
+            capture_directory: None,
+            capture_pre_s: 5.0,
+            capture_post_s: 5.0,
+            record_path: None,
+            record_format: RecordFormat::Pcm16,
+            record_segment_s: None,
+            metrics_listen: None,
+            live_listen: None,
+            live_rate_hz: 10.0,
+            mqtt: None,
+            osc: None,
+            statistics_windows_s: vec![60.0],
+        }
+    }
+}
+
+impl Config {
+    /// Read a JSON config file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let invalid = |err| io::Error::new(ErrorKind::InvalidData, err);
+        let value: serde_json::Value = serde_json::from_reader(BufReader::new(file)).map_err(invalid)?;
+        for key in retired_settings(&value) {
+            eprintln!(
+                "Warning: {} in {} is no longer used, set time_weighting (none, fast, slow or impulse) instead",
+                key,
+                path.display()
+            );
+        }
+        serde_json::from_value(value).map_err(invalid)
+    }
+
+    /// Read `path`, first writing the defaults there when it does not exist
+    pub fn load_or_create(path: &Path) -> io::Result<Self> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            let config = Self::default();
+            config.save(path)?;
+            Ok(config)
+        }
+    }
+
+    /// Write the settings as pretty-printed JSON
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    /// Check the settings that their types alone do not rule out
+    pub fn validate(&self) -> Result<(), String> {
+        if self.meter_width == 0 {
+            return Err("meter_width must be at least 1".to_string());
+        }
+        if self.block_ms == 0 {
+            return Err("block_ms must be at least 1".to_string());
+        }
+        if self.refresh_hz.is_nan() || self.refresh_hz <= 0.0 {
+            return Err("refresh_hz must be above 0".to_string());
+        }
+        if self.display_scale == DisplayScale::Linear && self.scale_min_db >= self.scale_max_db {
+            return Err("scale_min_db must be below scale_max_db".to_string());
+        }
+        // OSC bundles go out between blocks, so no faster than blocks arrive
+        if let Some(osc) = &self.osc {
+            let max_rate_hz = 1000.0 / self.block_ms.max(1) as f32;
+            if osc.rate_hz > max_rate_hz + 1e-3 {
+                return Err(format!(
+                    "osc.rate_hz {} is faster than the {} ms blocks allow, at most {}",
+                    osc.rate_hz, self.block_ms, max_rate_hz
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The display scale the settings describe
+    pub fn scale(&self) -> MeterScale {
+        MeterScale::new(self.display_scale, self.scale_min_db, self.scale_max_db)
+    }
+
+    /// Monitors for the configured alert rules, or for the plain threshold when none are given
+    pub fn alert_monitors(&self) -> Vec<AlertMonitor> {
+        if !self.alert_rules.is_empty() {
+            return self.alert_rules.iter().cloned().map(AlertMonitor::new).collect();
+        }
+        let (measure, threshold) = match self.alert_threshold_db {
+            Some(threshold) => (AlertMeasure::Db, threshold),
+            None => (AlertMeasure::Level, self.alert_threshold),
+        };
+        vec![AlertMonitor::new(AlertRule { name: "threshold".to_string(), measure, threshold, ..AlertRule::default() })]
+    }
+}
+
+// Settings older versions read that time_weighting has replaced
+const RETIRED_SETTINGS: [&str; 2] = ["use_moving_average", "moving_avg_size"];
+
+// Retired settings present in a config file, which would otherwise be ignored silently
+fn retired_settings(value: &serde_json::Value) -> Vec<&'static str> {
+    RETIRED_SETTINGS.into_iter().filter(|key| value.get(key).is_some()).collect()
+}
+
+/// How readings are shown while metering live
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    /// Meter redrawn in place on the current lines
+    Line,
+    /// Full-screen dashboard with history graph and key bindings
+    Tui,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn partial_files_keep_defaults() {
+        let path = std::env::temp_dir().join(format!("db_meter_config_{}.json", std::process::id()));
+        fs::write(&path, r#"{"meter_width": 60, "alert_threshold_db": -20.0, "mono_mode": "sum"}"#).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.meter_width, 60);
+        assert_eq!(config.mono_mode, MonoMode::Sum);
+        assert_eq!(config.block_ms, 100);
+
+        let monitors = config.alert_monitors();
+        assert_eq!(monitors.len(), 1);
+        assert_eq!((monitors[0].rule().measure, monitors[0].rule().threshold), (AlertMeasure::Db, -20.0));
+
+        let old: serde_json::Value = serde_json::from_str(r#"{"use_moving_average": true, "moving_avg_size": 5}"#).unwrap();
+        assert_eq!(retired_settings(&old), RETIRED_SETTINGS);
+        assert!(retired_settings(&serde_json::to_value(&config).unwrap()).is_empty());
+
+        fs::write(&path, "{\"meter_width\": \"wide\"}").unwrap();
+        assert_eq!(Config::load(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn creates_missing_file_with_defaults() {
+        let path = std::env::temp_dir().join(format!("db_meter_config_new_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let created = Config::load_or_create(&path).unwrap();
+        assert!(path.exists());
+        assert_eq!(Config::load(&path).unwrap().refresh_hz, created.refresh_hz);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_unusable_settings() {
+        let config = Config { scale_min_db: 0.0, scale_max_db: 0.0, ..Config::default() };
+        assert!(config.validate().is_err());
+        for config in [
+            Config { meter_width: 0, ..Config::default() },
+            Config { block_ms: 0, ..Config::default() },
+            Config { refresh_hz: 0.0, ..Config::default() },
+            Config { refresh_hz: f32::NAN, ..Config::default() },
+        ] {
+            assert!(config.validate().is_err(), "{:?}", config);
+        }
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_osc_rates_faster_than_blocks() {
+        let mut config = Config { osc: Some(OscSettings::default()), ..Config::default() };
+        assert!(config.validate().is_ok());
+        config.block_ms = 50;
+        config.osc.as_mut().unwrap().rate_hz = 30.0;
+        assert!(config.validate().unwrap_err().starts_with("osc.rate_hz 30"));
+        config.block_ms = 20;
+        assert!(config.validate().is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+
+/// Print every available host with its input devices and supported configs
+pub fn list_devices() {
+    let default_host = cpal::default_host().id();
+
+    for host_id in cpal::available_hosts() {
+        let marker = if host_id == default_host { " (default)" } else { "" };
+        println!("Host: {}{}", host_id.name(), marker);
+
+        let host = match cpal::host_from_id(host_id) {
+            Ok(host) => host,
+            Err(err) => {
+                println!("  unavailable: {}", err);
+                continue;
+            }
+        };
+
+        let default_name = host.default_input_device().and_then(|d| d.name().ok());
+        let devices = match host.input_devices() {
+            Ok(devices) => devices,
+            Err(err) => {
+                println!("  unable to enumerate input devices: {}", err);
+                continue;
+            }
+        };
+
+        for (index, device) in devices.enumerate() {
+            let name = device.name().unwrap_or_else(|_| "unknown device".to_string());
+            let marker = if Some(&name) == default_name.as_ref() { " (default)" } else { "" };
+            println!("  [{}] {}{}", index, name, marker);
+
+            match device.supported_input_configs() {
+                Ok(configs) => {
+                    for config in configs {
+                        let buffer = match config.buffer_size() {
+                            cpal::SupportedBufferSize::Range { min, max } => format!("{}-{} frames", min, max),
+                            cpal::SupportedBufferSize::Unknown => "unknown".to_string(),
+                        };
+                        println!(
+                            "      {} ch, {}-{} Hz, {}, buffer {}",
+                            config.channels(),
+                            config.min_sample_rate().0,
+                            config.max_sample_rate().0,
+                            config.sample_format(),
+                            buffer
+                        );
+                    }
+                }
+                Err(err) => println!("      unable to query configs: {}", err),
+            }
+        }
+    }
+}
+
+// Pick a host by case-insensitive name, falling back to the platform default
+fn select_host(name: Option<&str>) -> Result<cpal::Host, String> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+
+    let hosts = cpal::available_hosts();
+    match hosts.iter().find(|id| id.name().eq_ignore_ascii_case(name)) {
+        Some(&id) => cpal::host_from_id(id).map_err(|err| format!("Host '{}' is unavailable: {}", name, err)),
+        None => {
+            let available: Vec<&str> = hosts.iter().map(|id| id.name()).collect();
+            Err(format!("Host '{}' not found. Available hosts: {}", name, available.join(", ")))
+        }
+    }
+}
+
+// Indices of the devices `selector` picks out of `names`: an exact name, then
+// an index, then every case-insensitive substring match
+fn matching_devices(names: &[String], selector: &str) -> Vec<usize> {
+    if let Some(index) = names.iter().position(|name| name == selector) {
+        return vec![index];
+    }
+    match selector.parse::<usize>() {
+        Ok(index) if index < names.len() => vec![index],
+        _ => {
+            let needle = selector.to_lowercase();
+            (0..names.len())
+                .filter(|&i| names[i].to_lowercase().contains(&needle))
+                .collect()
+        }
+    }
+}
+
+/// Pick an input device by exact name, index or unique case-insensitive substring;
+/// without a selector the host's default input device is used
+pub fn select_input(host: Option<&str>, device: Option<&str>) -> Result<cpal::Device, String> {
+    let host = select_host(host)?;
+    let host_name = host.id().name();
+
+    let Some(selector) = device else {
+        return host
+            .default_input_device()
+            .ok_or_else(|| format!("Host {} has no default input device", host_name));
+    };
+
+    let mut devices: Vec<cpal::Device> = host
+        .input_devices()
+        .map_err(|err| format!("Unable to enumerate input devices on {}: {}", host_name, err))?
+        .collect();
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| d.name().unwrap_or_else(|_| "unknown device".to_string()))
+        .collect();
+    let listing = |indices: &[usize]| {
+        indices
+            .iter()
+            .map(|&index| format!("\n  [{}] {}", index, names[index]))
+            .collect::<String>()
+    };
+
+    let matches = matching_devices(&names, selector);
+    match matches.as_slice() {
+        [index] => Ok(devices.swap_remove(*index)),
+        [] => Err(format!(
+            "Input device '{}' not found on {}. Available input devices:{}",
+            selector,
+            host_name,
+            listing(&(0..names.len()).collect::<Vec<_>>())
+        )),
+        _ => Err(format!(
+            "Input device '{}' is ambiguous on {}. Matching devices:{}",
+            selector,
+            host_name,
+            listing(&matches)
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_devices_by_name_index_or_substring() {
+        let names: Vec<String> = ["USB Mic", "USB Mic 2", "Built-in Input", "7"].iter().map(|name| name.to_string()).collect();
+        assert_eq!(matching_devices(&names, "USB Mic"), [0]);
+        assert_eq!(matching_devices(&names, "2"), [2]);
+        assert_eq!(matching_devices(&names, "7"), [3]);
+        assert_eq!(matching_devices(&names, "built-in"), [2]);
+        assert_eq!(matching_devices(&names, "usb"), [0, 1]);
+        assert!(matching_devices(&names, "hdmi").is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_hosts() {
+        let err = select_host(Some("no such host")).err().unwrap();
+        assert!(err.starts_with("Host 'no such host' not found"), "{}", err);
+    }
+}
@@ -0,0 +1,57 @@
+/// Second-order IIR section in transposed direct form II, normalized so a0 = 1
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    /// Section with numerator `b` and denominator `a` coefficients
+    pub fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Magnitude response at `omega` radians per sample
+    pub fn magnitude(&self, omega: f64) -> f64 {
+        let (re1, im1) = (omega.cos(), -omega.sin());
+        let (re2, im2) = ((2.0 * omega).cos(), -(2.0 * omega).sin());
+        let numerator = (self.b0 + self.b1 * re1 + self.b2 * re2).hypot(self.b1 * im1 + self.b2 * im2);
+        let denominator = (1.0 + self.a1 * re1 + self.a2 * re2).hypot(self.a1 * im1 + self.a2 * im2);
+        numerator / denominator
+    }
+
+    /// Filter one sample
+    pub fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_and_filters() {
+        // One-pole smoothing y = 0.5 x + 0.5 y[n-1], given with a0 = 2
+        let mut biquad = Biquad::new([1.0, 0.0, 0.0], [2.0, -1.0, 0.0]);
+        assert!((biquad.magnitude(0.0) - 1.0).abs() < 1e-12);
+        let outputs: Vec<f64> = (0..3).map(|_| biquad.process(1.0)).collect();
+        assert_eq!(outputs, [0.5, 0.75, 0.875]);
+    }
+}
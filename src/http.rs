@@ -0,0 +1,92 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// Longest request head read from a client
+const MAX_REQUEST: usize = 8192;
+// Clients that stall for longer than this while sending a request are dropped
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The parts of an HTTP/1.1 request head the embedded servers look at
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String, // Without the query string
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    // Read a request head from `client`; any body is ignored
+    pub fn read(client: &mut TcpStream) -> io::Result<Self> {
+        client.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        client.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+        // Byte by byte so nothing after the head (such as WebSocket frames) is consumed
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") && head.len() < MAX_REQUEST {
+            if client.read(&mut byte)? == 0 {
+                break;
+            }
+            head.push(byte[0]);
+        }
+        Ok(Self::parse(&String::from_utf8_lossy(&head)))
+    }
+
+    fn parse(head: &str) -> Self {
+        let mut lines = head.lines();
+        let mut parts = lines.next().unwrap_or_default().split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let target = parts.next().unwrap_or_default();
+        let path = target.split('?').next().unwrap_or_default().to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        Self { method, path, headers }
+    }
+
+    // Value of header `name`, matched case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// Send a complete response and leave the connection to be closed
+pub fn respond(client: &mut TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        client,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    client.flush()
+}
+
+pub fn not_found(client: &mut TcpStream) -> io::Result<()> {
+    respond(client, "404 Not Found", "text/plain; charset=utf-8", "Not found\n")
+}
+
+pub fn method_not_allowed(client: &mut TcpStream) -> io::Result<()> {
+    respond(client, "405 Method Not Allowed", "text/plain; charset=utf-8", "Method not allowed\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_request_line_and_headers() {
+        let request = Request::parse("GET /ws?token=1 HTTP/1.1\r\nHost: meter\r\nSec-WebSocket-Key: abc==\r\n\r\n");
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/ws");
+        assert_eq!(request.header("sec-websocket-key"), Some("abc=="));
+        assert_eq!(request.header("Host"), Some("meter"));
+        assert_eq!(request.header("Upgrade"), None);
+    }
+}
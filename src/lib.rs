@@ -0,0 +1,60 @@
+//! Sound level metering: frequency and time weighting, RMS, peak and loudness
+//! measurements, statistics and alerts over any source of audio samples, with
+//! outputs for logs, recordings, Prometheus, browsers, MQTT and OSC.
+//!
+//! [`meter::AudioStream`] ties everything together; build it from a
+//! [`config::Config`] and feed it blocks of samples or a whole
+//! [`source::SampleSource`]. The building blocks can also be used on their own.
+
+#![warn(missing_docs)]
+
+/// Named alert rules and the monitors tracking them
+pub mod alert;
+/// Bar and peak-hold display dynamics
+pub mod ballistics;
+/// WAV clips of the audio around each alert
+pub mod capture;
+/// Settings as stored in config.json
+pub mod config;
+/// Listing and selecting input devices
+pub mod devices;
+/// Biquad filter sections
+pub mod filter;
+/// Live readings for browsers over Server-Sent Events and WebSocket
+pub mod live;
+/// Level log files in CSV or JSON lines
+pub mod logger;
+/// BS.1770 / EBU R128 loudness
+pub mod loudness;
+/// The meter driving the whole pipeline
+pub mod meter;
+/// Prometheus metrics endpoint
+pub mod metrics;
+/// Level summaries and alert events over MQTT
+pub mod mqtt;
+/// Levels, peaks and alerts as Open Sound Control over UDP
+pub mod osc;
+/// True-peak detection
+pub mod peak;
+/// Per-block RMS, peak and dB measurements
+pub mod processor;
+/// Recording the input to WAV
+pub mod record;
+/// Mapping dBFS readings onto the bar
+pub mod scale;
+/// Devices, files, stdin and generators delivering samples
+pub mod source;
+/// Leq, extremes and exceedance levels
+pub mod stats;
+/// Exponential time weighting (smoothing) of the RMS
+pub mod time_weighting;
+/// WAV reading and writing
+pub mod wav;
+/// A, C and Z frequency weighting
+pub mod weighting;
+
+mod http;
+mod ring;
+#[cfg(unix)]
+mod tui;
+mod websocket;
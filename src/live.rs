@@ -0,0 +1,385 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+use crate::http::{self, Request};
+use crate::websocket::{self, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG, OPCODE_TEXT};
+
+// Unanswered client frames are dropped past this size
+const MAX_CLIENT_BUFFER: usize = 64 * 1024;
+
+/// Direction of the main reading since the last display refresh
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trend {
+    /// Louder than at the last refresh
+    Up,
+    /// Quieter than at the last refresh
+    Down,
+    /// Unchanged since the last refresh
+    Steady,
+}
+
+impl Trend {
+    /// Trend from the `previous` level to `current`, steady without a previous one
+    pub fn between(previous: Option<f32>, current: f32) -> Self {
+        match previous {
+            Some(previous) if current > previous => Trend::Up,
+            Some(previous) if current < previous => Trend::Down,
+            _ => Trend::Steady,
+        }
+    }
+}
+
+/// Readings of one channel
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveChannel {
+    /// Channel name shown on the page
+    pub label: String,
+    /// Normalized 0-100 level
+    pub level: f32,
+    /// RMS reading in the meter's unit
+    pub db: f32,
+    /// Sample peak in dBFS
+    pub peak_db: f32,
+}
+
+/// What the meter line shows, as pushed to browsers. Values not measured yet
+/// are NaN and go out as null
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveReading {
+    /// Wall-clock time in UTC, RFC 3339
+    pub timestamp: String,
+    /// Audio time since metering started
+    pub elapsed_s: f64,
+    /// Unit of the main reading, e.g. "dB(A) SPL"
+    pub unit: String,
+    /// Normalized 0-100 level
+    pub level: f32,
+    /// Main reading in `unit`
+    pub db: f32,
+    /// Lowest level so far
+    pub min_level: f32,
+    /// Highest level so far
+    pub max_level: f32,
+    /// Lowest reading so far
+    pub min_db: f32,
+    /// Highest reading so far
+    pub max_db: f32,
+    /// Direction since the previous reading
+    pub trend: Trend,
+    /// Whether any alert rule is active
+    pub alert: bool,
+    /// Names of the active alert rules
+    pub alerts: Vec<String>,
+    /// Per-channel readings
+    pub channels: Vec<LiveChannel>,
+}
+
+/// Latest reading published by the metering thread; None until the first refresh
+pub type SharedReading = Arc<Mutex<Option<LiveReading>>>;
+
+/// Serves the live page on `/`, Server-Sent Events on `/events` and a WebSocket
+/// on `/ws`, each client on its own thread and sent the latest reading
+/// `rate_hz` times a second. Returns the bound address
+pub fn serve(address: &str, reading: SharedReading, rate_hz: f32) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local = listener.local_addr()?;
+    let interval = Duration::from_secs_f32(1.0 / rate_hz.max(0.1));
+    thread::spawn(move || {
+        for client in listener.incoming().flatten() {
+            let reading = Arc::clone(&reading);
+            // A client that goes away just ends its own thread
+            thread::spawn(move || {
+                let _ = respond(client, &reading, interval);
+            });
+        }
+    });
+    Ok(local)
+}
+
+fn respond(mut client: TcpStream, reading: &Mutex<Option<LiveReading>>, interval: Duration) -> io::Result<()> {
+    let request = Request::read(&mut client)?;
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => http::respond(&mut client, "200 OK", "text/html; charset=utf-8", PAGE),
+        ("GET", "/events") => stream_events(client, reading, interval),
+        ("GET", "/ws") => stream_websocket(client, &request, reading, interval),
+        ("GET", _) => http::not_found(&mut client),
+        _ => http::method_not_allowed(&mut client),
+    }
+}
+
+fn latest_json(reading: &Mutex<Option<LiveReading>>) -> Option<String> {
+    let reading = reading.lock().unwrap();
+    serde_json::to_string(reading.as_ref()?).ok()
+}
+
+fn stream_events(mut client: TcpStream, reading: &Mutex<Option<LiveReading>>, interval: Duration) -> io::Result<()> {
+    write!(
+        client,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: keep-alive\r\n\r\n"
+    )?;
+    client.flush()?;
+    // Runs until a write fails because the browser closed the stream
+    loop {
+        if let Some(json) = latest_json(reading) {
+            write!(client, "data: {}\n\n", json)?;
+            client.flush()?;
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn stream_websocket(
+    mut client: TcpStream,
+    request: &Request,
+    reading: &Mutex<Option<LiveReading>>,
+    interval: Duration,
+) -> io::Result<()> {
+    let upgrade = request.header("upgrade").is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    let Some(key) = request.header("sec-websocket-key").filter(|_| upgrade) else {
+        return http::respond(&mut client, "400 Bad Request", "text/plain; charset=utf-8", "Expected a WebSocket upgrade\n");
+    };
+    write!(
+        client,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        websocket::accept_key(key)
+    )?;
+    client.flush()?;
+
+    // Between sends, wait for client frames until the next send is due
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let mut next_send = Instant::now();
+    loop {
+        let now = Instant::now();
+        if now >= next_send {
+            if let Some(json) = latest_json(reading) {
+                client.write_all(&websocket::encode_frame(OPCODE_TEXT, json.as_bytes()))?;
+            }
+            next_send = now + interval;
+            continue;
+        }
+        client.set_read_timeout(Some(next_send - now))?;
+        match client.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(err) => return Err(err),
+        }
+        while let Some((frame, used)) = websocket::decode_frame(&buffer) {
+            buffer.drain(..used);
+            match frame.opcode {
+                OPCODE_CLOSE => {
+                    client.write_all(&websocket::encode_frame(OPCODE_CLOSE, &frame.payload))?;
+                    return Ok(());
+                }
+                OPCODE_PING => client.write_all(&websocket::encode_frame(OPCODE_PONG, &frame.payload))?,
+                _ => {} // Browsers have nothing else to say to a meter
+            }
+        }
+        if buffer.len() > MAX_CLIENT_BUFFER {
+            return Ok(());
+        }
+    }
+}
+
+// Live bars in the browser: WebSocket first, Server-Sent Events where that fails
+const PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>db_meter</title>
+<style>
+body { background: #111; color: #ddd; font: 14px monospace; margin: 2em; }
+.row { display: flex; align-items: center; margin: 0.4em 0; }
+.label { width: 3em; }
+.bar { flex: 1; height: 1.2em; background: #333; }
+.fill { height: 100%; width: 0; }
+.value { width: 12em; text-align: right; }
+#alert { color: #f44; font-weight: bold; min-height: 1.2em; }
+#status { color: #888; }
+</style>
+</head>
+<body>
+<div id="meters"></div>
+<p id="summary"></p>
+<p id="alert"></p>
+<p id="status">Connecting...</p>
+<script>
+const arrows = { up: "↑", down: "↓", steady: "→" };
+const fixed = value => value === null ? "-" : value.toFixed(1);
+
+function row(label, level, text) {
+  const color = level < 33 ? "#3c3" : level < 66 ? "#cc3" : "#c33";
+  const width = Math.max(0, Math.min(100, level || 0));
+  return `<div class="row"><span class="label">${label}</span>` +
+    `<div class="bar"><div class="fill" style="width: ${width}%; background: ${color}"></div></div>` +
+    `<span class="value">${text}</span></div>`;
+}
+
+function show(reading) {
+  const rows = reading.channels.map(channel =>
+    row(channel.label, channel.level, `${fixed(channel.db)} | pk ${fixed(channel.peak_db)}`));
+  if (reading.channels.length !== 1) {
+    rows.push(row("M", reading.level, `${fixed(reading.db)} ${reading.unit}`));
+  }
+  document.getElementById("meters").innerHTML = rows.join("");
+  document.getElementById("summary").textContent =
+    `${fixed(reading.db)} ${reading.unit} | Min: ${fixed(reading.min_db)} | Max: ${fixed(reading.max_db)} | ` +
+    `Trend: ${arrows[reading.trend]} | Elapsed: ${reading.elapsed_s.toFixed(1)}s`;
+  document.getElementById("alert").textContent = reading.alert ? `ALERT: ${reading.alerts.join(", ")}` : "";
+}
+
+function status(text) {
+  document.getElementById("status").textContent = text;
+}
+
+function events() {
+  const source = new EventSource("/events");
+  source.onopen = () => status("Live (Server-Sent Events)");
+  source.onmessage = event => show(JSON.parse(event.data));
+  source.onerror = () => status("Reconnecting...");
+}
+
+function connect() {
+  let opened = false;
+  let socket;
+  try {
+    socket = new WebSocket((location.protocol === "https:" ? "wss://" : "ws://") + location.host + "/ws");
+  } catch (error) {
+    return events();
+  }
+  socket.onopen = () => { opened = true; status("Live (WebSocket)"); };
+  socket.onmessage = event => show(JSON.parse(event.data));
+  socket.onclose = () => {
+    if (opened) {
+      status("Reconnecting...");
+      setTimeout(connect, 1000);
+    } else {
+      events();
+    }
+  };
+}
+
+connect();
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading() -> LiveReading {
+        LiveReading {
+            timestamp: "2024-05-01T12:30:00.250Z".to_string(),
+            elapsed_s: 1.5,
+            unit: "dB(A)".to_string(),
+            level: 62.5,
+            db: -37.5,
+            min_level: 10.0,
+            max_level: 70.0,
+            min_db: f32::NAN,
+            max_db: -30.0,
+            trend: Trend::between(Some(60.0), 62.5),
+            alert: true,
+            alerts: vec!["loud".to_string()],
+            channels: vec![LiveChannel { label: "L".to_string(), level: 62.5, db: -37.5, peak_db: -30.0 }],
+        }
+    }
+
+    fn connect(address: SocketAddr, request: &str) -> TcpStream {
+        let mut client = TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        client
+    }
+
+    // Read until `end` has arrived, returning everything read
+    fn read_until(client: &mut TcpStream, end: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut byte = [0u8; 1];
+        while !data.ends_with(end) {
+            assert_eq!(client.read(&mut byte).unwrap(), 1, "{}", String::from_utf8_lossy(&data));
+            data.push(byte[0]);
+        }
+        data
+    }
+
+    #[test]
+    fn streams_server_sent_events() {
+        let shared = Arc::new(Mutex::new(Some(reading())));
+        let address = serve("127.0.0.1:0", shared, 50.0).unwrap();
+
+        let mut client = connect(address, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let head = String::from_utf8(read_until(&mut client, b"\r\n\r\n")).unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains("Content-Type: text/event-stream\r\n"));
+
+        for _ in 0..2 {
+            let event = String::from_utf8(read_until(&mut client, b"\n\n")).unwrap();
+            let json: serde_json::Value = serde_json::from_str(event.strip_prefix("data: ").unwrap().trim()).unwrap();
+            assert_eq!(json["trend"], "up");
+            assert_eq!(json["alerts"][0], "loud");
+            assert!(json["min_db"].is_null());
+            assert_eq!(json["channels"][0]["label"], "L");
+        }
+    }
+
+    #[test]
+    fn streams_over_websocket() {
+        let shared = Arc::new(Mutex::new(Some(reading())));
+        let address = serve("127.0.0.1:0", shared, 50.0).unwrap();
+
+        let mut client = connect(
+            address,
+            "GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        );
+        let head = String::from_utf8(read_until(&mut client, b"\r\n\r\n")).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{}", head);
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let mut data = Vec::new();
+        let frame = loop {
+            if let Some((frame, _)) = websocket::decode_frame(&data) {
+                break frame;
+            }
+            let mut chunk = [0u8; 256];
+            let read = client.read(&mut chunk).unwrap();
+            assert!(read > 0);
+            data.extend_from_slice(&chunk[..read]);
+        };
+        assert_eq!(frame.opcode, OPCODE_TEXT);
+        let json: serde_json::Value = serde_json::from_slice(&frame.payload).unwrap();
+        assert_eq!(json["db"], -37.5);
+
+        // A masked close from the client is echoed before the server hangs up
+        client.write_all(&[0x88, 0x80, 1, 2, 3, 4]).unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).unwrap();
+        assert!(rest.ends_with(&[0x88, 0x00]), "{:?}", rest);
+    }
+
+    #[test]
+    fn serves_page_and_rejects_plain_ws_requests() {
+        let address = serve("127.0.0.1:0", Arc::new(Mutex::new(None)), 10.0).unwrap();
+
+        let mut client = connect(address, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut page = String::new();
+        client.read_to_string(&mut page).unwrap();
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(page.contains("new EventSource(\"/events\")"));
+
+        let mut client = connect(address, "GET /ws HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
@@ -0,0 +1,354 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+/// File format of the level log
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Comma-separated values with a header row
+    #[default]
+    Csv,
+    /// One JSON object per line
+    JsonLines,
+}
+
+/// Where, how often and how long each file is written
+#[derive(Debug, Clone)]
+pub struct LogSettings {
+    /// Current log file; rotated files are named after it
+    pub path: PathBuf,
+    /// CSV or JSON Lines
+    pub format: LogFormat,
+    /// Seconds of audio between records, 0 logs every block
+    pub interval_s: f64,
+    /// Start a new file once this size is reached
+    pub rotate_bytes: Option<u64>,
+    /// Start a new file after this many seconds
+    pub rotate_s: Option<f64>,
+}
+
+/// Readings of one channel in a record
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelRecord {
+    /// RMS reading in the meter's unit
+    pub db: f32,
+    /// Normalized 0-100 level
+    pub level: f32,
+}
+
+/// One timestamped line of the level log; non-finite values are left empty in
+/// CSV and written as null in JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelRecord {
+    /// Wall-clock time in UTC, RFC 3339
+    pub timestamp: String,
+    /// Audio time since metering started
+    pub elapsed_s: f64,
+    /// Main reading in the display unit (dB SPL when calibrated)
+    pub db: f32,
+    /// Main reading without the calibration offset
+    pub dbfs: f32,
+    /// Normalized 0-100 level
+    pub level: f32,
+    /// Normalized 0-100 after the bar ballistics
+    pub smoothed_level: f32,
+    /// Lowest reading so far
+    pub min_db: f32,
+    /// Highest reading so far
+    pub max_db: f32,
+    /// Whether any alert rule is active
+    pub alert: bool,
+    /// Readings of each channel
+    pub channels: Vec<ChannelRecord>,
+}
+
+/// Writes level records at a fixed interval, rotating the file by size or age.
+/// The current file always lives at the configured path; rotated files get the
+/// time they were closed inserted before the extension
+pub struct LevelLogger {
+    settings: LogSettings,
+    file: Option<BufWriter<File>>,
+    written: u64,
+    opened_at_s: f64,
+    next_record_s: f64,
+}
+
+impl LevelLogger {
+    /// Open (appending to) the log file so configuration mistakes show up at startup
+    pub fn new(settings: LogSettings) -> io::Result<Self> {
+        let mut logger = Self { settings, file: None, written: 0, opened_at_s: 0.0, next_record_s: 0.0 };
+        logger.open(0.0)?;
+        Ok(logger)
+    }
+
+    /// Whether a record is due at `elapsed_s`; the tolerance keeps rounding in the
+    /// interval grid from skipping a record
+    pub fn due(&self, elapsed_s: f64) -> bool {
+        elapsed_s >= self.next_record_s - 1e-9
+    }
+
+    /// Append `record`, first rotating the file when it has grown too large or old
+    pub fn write(&mut self, record: &LevelRecord) -> io::Result<()> {
+        let rotate_by_size = self.settings.rotate_bytes.is_some_and(|bytes| self.written >= bytes);
+        let rotate_by_time = self.settings.rotate_s.is_some_and(|seconds| record.elapsed_s - self.opened_at_s >= seconds);
+        if rotate_by_size || rotate_by_time {
+            self.rotate(record.elapsed_s)?;
+        }
+
+        let mut line = String::new();
+        if self.settings.format == LogFormat::Csv && self.written == 0 {
+            line.push_str(&csv_header(record.channels.len()));
+            line.push('\n');
+        }
+        match self.settings.format {
+            LogFormat::Csv => line.push_str(&csv_row(record)),
+            LogFormat::JsonLines => line.push_str(&serde_json::to_string(record).map_err(io::Error::other)?),
+        }
+        line.push('\n');
+        let file = self.file.as_mut().expect("Level log is open");
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        self.written += line.len() as u64;
+
+        // Stay on the interval grid rather than drifting with block boundaries
+        let interval = self.settings.interval_s;
+        self.next_record_s = if interval > 0.0 {
+            ((record.elapsed_s / interval).floor() + 1.0) * interval
+        } else {
+            record.elapsed_s
+        };
+        Ok(())
+    }
+
+    fn open(&mut self, elapsed_s: f64) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(&self.settings.path)?;
+        self.written = file.metadata()?.len();
+        self.file = Some(BufWriter::new(file));
+        self.opened_at_s = elapsed_s;
+        Ok(())
+    }
+
+    fn rotate(&mut self, elapsed_s: f64) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        // Stamped to the millisecond, with a counter for rotations even closer together
+        let stamp = timestamp(SystemTime::now()).replace([':', '-', '.'], "");
+        let mut rotated = rotated_path(&self.settings.path, &stamp[..18]);
+        let mut count = 1;
+        while rotated.exists() {
+            count += 1;
+            rotated = rotated_path(&self.settings.path, &format!("{}-{}", &stamp[..18], count));
+        }
+        fs::rename(&self.settings.path, rotated)?;
+        self.open(elapsed_s)
+    }
+}
+
+/// Header row for a CSV file with `channels` channels
+pub fn csv_header(channels: usize) -> String {
+    let mut columns = vec![
+        "timestamp", "elapsed_s", "db", "dbfs", "level", "smoothed_level", "min_db", "max_db", "alert",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect::<Vec<_>>();
+    for channel in 1..=channels {
+        columns.push(format!("ch{}_db", channel));
+        columns.push(format!("ch{}_level", channel));
+    }
+    columns.join(",")
+}
+
+fn csv_row(record: &LevelRecord) -> String {
+    let number = |value: f32| if value.is_finite() { format!("{:.2}", value) } else { String::new() };
+    let mut fields = vec![
+        record.timestamp.clone(),
+        format!("{:.3}", record.elapsed_s),
+        number(record.db),
+        number(record.dbfs),
+        number(record.level),
+        number(record.smoothed_level),
+        number(record.min_db),
+        number(record.max_db),
+        (record.alert as u8).to_string(),
+    ];
+    for channel in &record.channels {
+        fields.push(number(channel.db));
+        fields.push(number(channel.level));
+    }
+    fields.join(",")
+}
+
+// "levels.csv" rotated at `stamp` becomes "levels.<stamp>.csv"
+fn rotated_path(path: &Path, stamp: &str) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, stamp, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, stamp),
+    };
+    path.with_file_name(name)
+}
+
+/// RFC 3339 UTC timestamp with milliseconds, e.g. "2024-05-01T12:30:00.250Z"
+pub fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, second_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn record(elapsed_s: f64) -> LevelRecord {
+        LevelRecord {
+            timestamp: "2024-01-01T00:00:00.000Z".to_string(),
+            elapsed_s,
+            db: -20.0,
+            dbfs: -20.0,
+            level: 80.0,
+            smoothed_level: 79.5,
+            min_db: f32::NAN,
+            max_db: -10.0,
+            alert: true,
+            channels: vec![ChannelRecord { db: -20.0, level: 80.0 }, ChannelRecord { db: f32::NEG_INFINITY, level: 0.0 }],
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("db_meter_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let leap_day = UNIX_EPOCH + Duration::from_millis(951_782_400_000 + 3_723_456);
+        assert_eq!(timestamp(leap_day), "2000-02-29T01:02:03.456Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_735_689_599)), "2024-12-31T23:59:59.000Z");
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let header = csv_header(2);
+        let row = csv_row(&record(1.5));
+        assert_eq!(header.split(',').count(), row.split(',').count());
+        assert_eq!(row, "2024-01-01T00:00:00.000Z,1.500,-20.00,-20.00,80.00,79.50,,-10.00,1,-20.00,80.00,,0.00");
+    }
+
+    #[test]
+    fn json_lines_use_null_for_missing_values() {
+        let line = serde_json::to_string(&record(0.0)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert!(value["min_db"].is_null());
+        assert_eq!(value["channels"][0]["level"], 80.0);
+        assert_eq!(value["alert"], true);
+    }
+
+    #[test]
+    fn records_follow_the_interval() {
+        let dir = temp_dir("interval");
+        let settings = LogSettings {
+            path: dir.join("levels.jsonl"),
+            format: LogFormat::JsonLines,
+            interval_s: 0.5,
+            rotate_bytes: None,
+            rotate_s: None,
+        };
+        let mut logger = LevelLogger::new(settings).unwrap();
+        let mut written = 0;
+        for block in 0..50 {
+            let elapsed_s = block as f64 * 0.1;
+            if logger.due(elapsed_s) {
+                logger.write(&record(elapsed_s)).unwrap();
+                written += 1;
+            }
+        }
+        assert_eq!(written, 10);
+        let contents = fs::read_to_string(dir.join("levels.jsonl")).unwrap();
+        assert_eq!(contents.lines().count(), 10);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = temp_dir("rotate");
+        let settings = LogSettings {
+            path: dir.join("levels.csv"),
+            format: LogFormat::Csv,
+            interval_s: 0.0,
+            rotate_bytes: Some(200),
+            rotate_s: None,
+        };
+        let mut logger = LevelLogger::new(settings).unwrap();
+        for block in 0..3 {
+            logger.write(&record(block as f64)).unwrap();
+        }
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 2, "{:?}", names);
+        assert!(names.iter().any(|name| name == "levels.csv"));
+        assert!(names.iter().any(|name| name.starts_with("levels.") && name.ends_with(".csv") && name.len() > 12));
+        // Every file starts with its own header
+        for name in names {
+            let contents = fs::read_to_string(dir.join(name)).unwrap();
+            assert_eq!(contents.lines().next().unwrap(), csv_header(2));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotations_in_the_same_instant_keep_every_file() {
+        let dir = temp_dir("rotate_fast");
+        let settings = LogSettings {
+            path: dir.join("levels.csv"),
+            format: LogFormat::Csv,
+            interval_s: 0.0,
+            rotate_bytes: Some(1),
+            rotate_s: None,
+        };
+        let mut logger = LevelLogger::new(settings).unwrap();
+        for block in 0..5 {
+            logger.write(&record(block as f64)).unwrap();
+        }
+        // Every row went to a file of its own and none was overwritten
+        let rows: usize = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap().lines().count() - 1)
+            .sum();
+        assert_eq!(rows, 5);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+use crate::filter::Biquad;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const INTEGRATED_RELATIVE_GATE_LU: f64 = -10.0;
+const RANGE_RELATIVE_GATE_LU: f64 = -20.0;
+const MOMENTARY_STEPS: usize = 4; // 400 ms window in 100 ms steps
+const SHORT_TERM_STEPS: usize = 30; // 3 s window in 100 ms steps
+const HISTOGRAM_MAX_LUFS: f64 = 30.0; // Louder blocks share the top bin
+const HISTOGRAM_BINS_PER_LU: f64 = 10.0;
+
+/// Loudness readings in LUFS (LU for the range); NEG_INFINITY until enough
+/// audio has been measured to fill the window
+#[derive(Debug, Clone, Copy)]
+pub struct Loudness {
+    /// Momentary loudness over the last 400 ms
+    pub momentary: f64,
+    /// Short-term loudness over the last 3 s
+    pub short_term: f64,
+    /// Gated integrated loudness since the start
+    pub integrated: f64,
+    /// Loudness range in LU
+    pub range: f64,
+}
+
+fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| (sum + value, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+// BS.1770 K-weighting (high-shelf "pre-filter" followed by the RLB high-pass),
+// designed for an arbitrary sample rate
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let shelf = Biquad::new(
+        [vh + vb * k / q + k * k, 2.0 * (k * k - vh), vh - vb * k / q + k * k],
+        [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    // The RLB stage keeps a unity numerator, only the denominator is normalized
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+// Per-channel weights: 1.0 for front channels, 1.41 for surrounds, LFE excluded
+fn channel_weights(channels: usize) -> Vec<f64> {
+    match channels {
+        5 => vec![1.0, 1.0, 1.0, 1.41, 1.41],
+        6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+        _ => vec![1.0; channels],
+    }
+}
+
+// Gating blocks above the absolute gate, binned by loudness so memory and the
+// cost of a reading stay fixed however long the meter runs. Each bin keeps the
+// exact energy of its blocks; only which side of the relative gate a block
+// falls on is resolved to the bin width
+struct GatingHistogram {
+    counts: Vec<u64>,
+    energies: Vec<f64>,
+}
+
+impl GatingHistogram {
+    fn new() -> Self {
+        let bins = ((HISTOGRAM_MAX_LUFS - ABSOLUTE_GATE_LUFS) * HISTOGRAM_BINS_PER_LU) as usize;
+        Self { counts: vec![0; bins], energies: vec![0.0; bins] }
+    }
+
+    fn add(&mut self, energy: f64) {
+        let lufs = energy_to_lufs(energy);
+        if lufs <= ABSOLUTE_GATE_LUFS {
+            return;
+        }
+        let bin = (((lufs - ABSOLUTE_GATE_LUFS) * HISTOGRAM_BINS_PER_LU) as usize).min(self.counts.len() - 1);
+        self.counts[bin] += 1;
+        self.energies[bin] += energy;
+    }
+
+    // (count, loudness of the mean energy) of each occupied bin, quietest first
+    fn bins(&self) -> impl Iterator<Item = (u64, f64)> + '_ {
+        self.counts
+            .iter()
+            .zip(&self.energies)
+            .filter(|(&count, _)| count > 0)
+            .map(|(&count, &energy)| (count, energy_to_lufs(energy / count as f64)))
+    }
+
+    // Mean-energy loudness of the bins above `gate`, None when there are none
+    fn gated_loudness(&self, gate: f64) -> Option<f64> {
+        let (count, energy) = self
+            .counts
+            .iter()
+            .zip(&self.energies)
+            .filter(|(&count, &energy)| count > 0 && energy_to_lufs(energy / count as f64) > gate)
+            .fold((0, 0.0), |(count, sum), (&bin_count, &energy)| (count + bin_count, sum + energy));
+        (count > 0).then(|| energy_to_lufs(energy / count as f64))
+    }
+}
+
+/// Gated loudness meter following ITU-R BS.1770-4 and EBU R128 / Tech 3342
+pub struct LoudnessMeter {
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    step_length: usize,
+    step_position: usize,
+    step_energy: f64,
+    steps: VecDeque<f64>,
+    momentary_blocks: GatingHistogram,
+    short_term_blocks: GatingHistogram,
+}
+
+impl LoudnessMeter {
+    /// Meter for `channels` channels at `sample_rate`, weighted as BS.1770 specifies for 5.0 and 5.1 layouts
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            filters: vec![k_weighting(sample_rate as f64); channels],
+            weights: channel_weights(channels),
+            step_length: (sample_rate as usize / 10).max(1),
+            step_position: 0,
+            step_energy: 0.0,
+            steps: VecDeque::with_capacity(SHORT_TERM_STEPS + 1),
+            momentary_blocks: GatingHistogram::new(),
+            short_term_blocks: GatingHistogram::new(),
+        }
+    }
+
+    /// Feed one block of de-interleaved channel buffers of equal length
+    pub fn process(&mut self, channels: &[Vec<f32>]) {
+        let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+        for frame in 0..frames {
+            for ((samples, filters), weight) in channels.iter().zip(&mut self.filters).zip(&self.weights) {
+                let shelved = filters[0].process(samples[frame] as f64);
+                let weighted = filters[1].process(shelved);
+                self.step_energy += weight * weighted * weighted;
+            }
+
+            self.step_position += 1;
+            if self.step_position == self.step_length {
+                self.finish_step();
+            }
+        }
+    }
+
+    // Close a 100 ms step; gating blocks overlap by 75% so every step completes one
+    fn finish_step(&mut self) {
+        self.steps.push_back(self.step_energy / self.step_length as f64);
+        if self.steps.len() > SHORT_TERM_STEPS {
+            self.steps.pop_front();
+        }
+        self.step_energy = 0.0;
+        self.step_position = 0;
+
+        if self.steps.len() >= MOMENTARY_STEPS {
+            self.momentary_blocks.add(self.window_energy(MOMENTARY_STEPS));
+        }
+        if self.steps.len() == SHORT_TERM_STEPS {
+            self.short_term_blocks.add(self.window_energy(SHORT_TERM_STEPS));
+        }
+    }
+
+    fn window_energy(&self, steps: usize) -> f64 {
+        mean(self.steps.iter().rev().take(steps).copied())
+    }
+
+    fn windowed_loudness(&self, steps: usize) -> f64 {
+        if self.steps.len() < steps {
+            f64::NEG_INFINITY
+        } else {
+            energy_to_lufs(self.window_energy(steps))
+        }
+    }
+
+    /// Loudness over the last 400 ms
+    pub fn momentary(&self) -> f64 {
+        self.windowed_loudness(MOMENTARY_STEPS)
+    }
+
+    /// Loudness over the last 3 s
+    pub fn short_term(&self) -> f64 {
+        self.windowed_loudness(SHORT_TERM_STEPS)
+    }
+
+    /// Integrated loudness over everything measured so far, with the absolute
+    /// and -10 LU relative gates applied
+    pub fn integrated(&self) -> f64 {
+        match self.momentary_blocks.gated_loudness(ABSOLUTE_GATE_LUFS) {
+            Some(ungated) => self
+                .momentary_blocks
+                .gated_loudness(ungated + INTEGRATED_RELATIVE_GATE_LU)
+                .unwrap_or(f64::NEG_INFINITY),
+            None => f64::NEG_INFINITY,
+        }
+    }
+
+    /// Loudness range (EBU Tech 3342): spread between the 10th and 95th percentile
+    /// of the gated short-term loudness distribution
+    pub fn loudness_range(&self) -> f64 {
+        let Some(ungated) = self.short_term_blocks.gated_loudness(ABSOLUTE_GATE_LUFS) else {
+            return 0.0;
+        };
+        let relative_gate = ungated + RANGE_RELATIVE_GATE_LU;
+        let levels: Vec<(u64, f64)> = self.short_term_blocks.bins().filter(|&(_, lufs)| lufs > relative_gate).collect();
+        let blocks: u64 = levels.iter().map(|&(count, _)| count).sum();
+        if blocks == 0 {
+            return 0.0;
+        }
+
+        let percentile = |p: f64| {
+            let rank = ((blocks - 1) as f64 * p).round() as u64;
+            let mut seen = 0;
+            for &(count, lufs) in &levels {
+                seen += count;
+                if seen > rank {
+                    return lufs;
+                }
+            }
+            levels[levels.len() - 1].1
+        };
+        percentile(0.95) - percentile(0.10)
+    }
+
+    /// All readings at once
+    pub fn loudness(&self) -> Loudness {
+        Loudness {
+            momentary: self.momentary(),
+            short_term: self.short_term(),
+            integrated: self.integrated(),
+            range: self.loudness_range(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    // Feed `seconds` of a stereo 1 kHz sine at `dbfs` peak level in 100 ms blocks,
+    // the way the EBU Tech 3341/3342 test signals are specified
+    fn feed_sine(meter: &mut LoudnessMeter, dbfs: f64, seconds: f64) {
+        let amplitude = 10f64.powf(dbfs / 20.0);
+        let frames = (seconds * SAMPLE_RATE as f64).round() as usize;
+        let block = SAMPLE_RATE as usize / 10;
+        let mut start = 0;
+        while start < frames {
+            let end = (start + block).min(frames);
+            let samples: Vec<f32> = (start..end)
+                .map(|n| (amplitude * (2.0 * PI * 1000.0 * n as f64 / SAMPLE_RATE as f64).sin()) as f32)
+                .collect();
+            meter.process(&[samples.clone(), samples]);
+            start = end;
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} +/- {}, got {}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+
+    #[test]
+    fn tech_3341_case_1_reads_minus_23() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        feed_sine(&mut meter, -23.0, 20.0);
+        let loudness = meter.loudness();
+        assert_close(loudness.momentary, -23.0, 0.1);
+        assert_close(loudness.short_term, -23.0, 0.1);
+        assert_close(loudness.integrated, -23.0, 0.1);
+    }
+
+    #[test]
+    fn tech_3341_case_2_reads_minus_33() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        feed_sine(&mut meter, -33.0, 20.0);
+        let loudness = meter.loudness();
+        assert_close(loudness.momentary, -33.0, 0.1);
+        assert_close(loudness.short_term, -33.0, 0.1);
+        assert_close(loudness.integrated, -33.0, 0.1);
+    }
+
+    #[test]
+    fn tech_3341_case_3_relative_gate() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        feed_sine(&mut meter, -36.0, 10.0);
+        feed_sine(&mut meter, -23.0, 60.0);
+        feed_sine(&mut meter, -36.0, 10.0);
+        assert_close(meter.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn tech_3341_case_4_absolute_gate() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        feed_sine(&mut meter, -72.0, 10.0);
+        feed_sine(&mut meter, -36.0, 10.0);
+        feed_sine(&mut meter, -23.0, 60.0);
+        feed_sine(&mut meter, -36.0, 10.0);
+        feed_sine(&mut meter, -72.0, 10.0);
+        assert_close(meter.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn tech_3342_case_1_loudness_range() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        feed_sine(&mut meter, -20.0, 20.0);
+        feed_sine(&mut meter, -30.0, 20.0);
+        assert_close(meter.loudness_range(), 10.0, 1.0);
+    }
+
+    #[test]
+    fn gating_blocks_are_binned_not_kept() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        feed_sine(&mut meter, -23.0, 30.0);
+        feed_sine(&mut meter, -80.0, 10.0);
+        // About 300 momentary blocks pass the absolute gate, the silence none,
+        // and the histogram keeps its size
+        let histogram = &meter.momentary_blocks;
+        assert_eq!(histogram.counts.len(), 1000);
+        assert!((297..=301).contains(&histogram.counts.iter().sum::<u64>()));
+        assert_close(meter.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn windows_are_undefined_until_filled() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        feed_sine(&mut meter, -23.0, 0.3);
+        assert_eq!(meter.momentary(), f64::NEG_INFINITY);
+        feed_sine(&mut meter, -23.0, 0.1);
+        assert!(meter.momentary().is_finite());
+        assert_eq!(meter.short_term(), f64::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,1898 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+
+use crate::alert::{AlertEvent, AlertMeasure, AlertMonitor, AlertReading};
+use crate::ballistics::{BallisticsSettings, BarBallistics};
+use crate::capture::{CaptureSettings, ClipLevel, EventCapture};
+use crate::config::Config;
+use crate::live::{self, LiveChannel, LiveReading, SharedReading, Trend};
+use crate::logger::{self, ChannelRecord, LevelLogger, LevelRecord, LogSettings};
+use crate::loudness::Loudness;
+use crate::metrics::{self, AlertMetrics, ChannelMetrics, Metrics};
+use crate::mqtt::{LevelSummary, MqttPublisher};
+use crate::osc::{OscChannel, OscSender};
+use crate::peak::TruePeakFilter;
+use crate::processor::{AudioProcessor, MeterQuantity, SoundProcessor};
+use crate::record::{RecordSettings, Recorder};
+use crate::ring::ring_buffer;
+use crate::scale::MeterScale;
+use crate::source::{SampleSource, SourceFormat};
+use crate::stats::{LevelStatistics, StatisticsSummary, EXCEEDANCE_PERCENTS};
+use crate::time_weighting::{TimeWeightedLevel, TimeWeighting};
+#[cfg(unix)]
+use crate::tui::{self, Dashboard, Key, Terminal};
+use crate::weighting::{Weighting, WeightingFilter};
+
+/// How a combined mono level is derived from the individual channels
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonoMode {
+    /// Channels are metered separately
+    Off,
+    /// Channels summed to mono (averaged so identical channels read the same)
+    Sum,
+    /// Loudest channel
+    Max,
+}
+
+/// Levels measured on one channel for the latest block
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelLevel {
+    /// RMS reading, dB SPL once calibrated
+    pub db: f32,
+    /// Sample peak in dBFS
+    pub peak_db: f32,
+    /// True peak in dBTP
+    pub true_peak_db: f32,
+    /// Normalized 0-100 level
+    pub level: f32,
+}
+
+impl Default for ChannelLevel {
+    fn default() -> Self {
+        Self {
+            db: f32::NEG_INFINITY,
+            peak_db: f32::NEG_INFINITY,
+            true_peak_db: f32::NEG_INFINITY,
+            level: 0.0,
+        }
+    }
+}
+
+// Per-channel state carried from one block to the next
+struct ChannelState {
+    time_weighting: Option<TimeWeightedLevel>,
+    true_peak: TruePeakFilter,
+    weighting: Option<WeightingFilter>,
+    weighted: Vec<f32>,
+}
+
+// Failures of the outputs fed while metering, which must not stop it: each kind
+// is reported the first time it happens and counted from then on
+#[derive(Default)]
+struct OutputErrors {
+    reported: Vec<&'static str>,
+    count: u64,
+}
+
+impl OutputErrors {
+    fn report(&mut self, what: &'static str, err: io::Error) {
+        self.count += 1;
+        if !self.reported.contains(&what) {
+            self.reported.push(what);
+            eprintln!("{}: {} (metering continues, further errors are counted)", what, err);
+        }
+    }
+}
+
+/// The meter itself: turns blocks of interleaved samples into per-channel and
+/// main readings, statistics and alerts, and feeds them to every configured
+/// output. Build one with [`AudioStream::from_config`], then either hand it
+/// blocks through [`AudioStream::process_block`] or let it run a whole source
+pub struct AudioStream {
+    processor: Box<dyn SoundProcessor + Send>,
+    meter_quantity: MeterQuantity,
+    meter_width: usize,
+    block_ms: u32,
+    refresh_hz: f32,
+    dropped_frames: u64, // Frames the audio callback could not queue
+    stream_errors: u64,  // Errors the input stream reported
+    output_errors: OutputErrors,
+    metered_frames: u64, // Frames processed since metering started, the log's clock
+    duration_s: Option<f64>, // Stop after this much audio
+    alerts_fired: usize,     // Alert triggers since the meter was built, kept across resets
+    logger: Option<LevelLogger>,
+    scale: MeterScale,
+    ballistics: BallisticsSettings,
+    bars: Vec<BarBallistics>,       // One per channel plus the main reading
+    audio_bars: Vec<BarBallistics>, // The same on the audio's clock, for the level log and metrics
+    channel_states: Vec<ChannelState>,
+    time_weighting: TimeWeighting,
+    weighting: Weighting,
+    mono_mode: MonoMode,
+    channels: usize,
+    sample_rate: u32,
+    channel_buffers: Vec<Vec<f32>>,
+    channel_levels: Vec<ChannelLevel>,
+    mono_level: ChannelLevel,
+    loudness: Option<Loudness>,
+    lines_drawn: usize,
+    min_level: f32,
+    max_level: f32,
+    max_peak_db: f32,
+    max_true_peak_db: f32,
+    current_level: f32,
+    min_db: f32,
+    max_db: f32,
+    statistics: LevelStatistics,
+    window_statistics: Vec<LevelStatistics>,
+    alerts: Vec<AlertMonitor>,
+    capture: Option<EventCapture>,
+    record: Option<RecordSettings>,
+    recorder: Option<Recorder>,
+    metrics: Option<Arc<Mutex<Metrics>>>,
+    live: Option<SharedReading>,
+    mqtt: Option<MqttPublisher>,
+    mqtt_statistics: LevelStatistics, // Readings since the last MQTT level summary
+    osc: Option<OscSender>,
+    calibration_offset_db: f32,
+    start_time: Instant,
+    prev_level: Option<f32>,
+}
+
+impl Default for AudioStream {
+    fn default() -> Self {
+        Self {
+            processor: Box::new(AudioProcessor::new(MeterScale::default())),
+            meter_quantity: MeterQuantity::Rms,
+            meter_width: 100,
+            block_ms: 100,
+            refresh_hz: 20.0,
+            dropped_frames: 0,
+            stream_errors: 0,
+            output_errors: OutputErrors::default(),
+            metered_frames: 0,
+            duration_s: None,
+            alerts_fired: 0,
+            logger: None,
+            scale: MeterScale::default(),
+            ballistics: BallisticsSettings { attack_s: 0.0, release_s: 0.0, hold_s: 0.0, decay_db_per_s: 0.0 },
+            bars: Vec::new(),
+            audio_bars: Vec::new(),
+            channel_states: Vec::new(),
+            time_weighting: TimeWeighting::Fast,
+            weighting: Weighting::Z,
+            mono_mode: MonoMode::Off,
+            channels: 0,
+            sample_rate: 48_000,
+            channel_buffers: Vec::new(),
+            channel_levels: Vec::new(),
+            mono_level: ChannelLevel::default(),
+            loudness: None,
+            lines_drawn: 0,
+            min_level: f32::MAX,
+            max_level: f32::MIN,
+            max_peak_db: f32::NEG_INFINITY,
+            max_true_peak_db: f32::NEG_INFINITY,
+            current_level: 0.0,
+            min_db: f32::MAX,
+            max_db: f32::MIN,
+            statistics: LevelStatistics::default(),
+            window_statistics: Vec::new(),
+            alerts: Vec::new(),
+            capture: None,
+            record: None,
+            recorder: None,
+            metrics: None,
+            live: None,
+            mqtt: None,
+            mqtt_statistics: LevelStatistics::default(),
+            osc: None,
+            calibration_offset_db: 0.0,
+            start_time: Instant::now(),
+            prev_level: None,
+        }
+    }
+}
+
+impl AudioStream {
+    /// Build a meter from `config`, opening its level log and capture directory
+    /// and starting the metrics, live, MQTT and OSC outputs it asks for
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let scale = config.scale();
+        let mut stream = AudioStream {
+            processor: config.meter_quantity.processor(scale),
+            meter_quantity: config.meter_quantity,
+            meter_width: config.meter_width,
+            block_ms: config.block_ms,
+            refresh_hz: config.refresh_hz,
+            duration_s: config.duration_s,
+            scale,
+            ballistics: BallisticsSettings {
+                attack_s: config.bar_attack_ms / 1000.0,
+                release_s: config.bar_release_ms / 1000.0,
+                hold_s: config.peak_hold_s,
+                decay_db_per_s: config.peak_decay_db_per_s,
+            },
+            time_weighting: config.time_weighting,
+            mono_mode: config.mono_mode,
+            weighting: config.weighting,
+            alerts: config.alert_monitors(),
+            record: config.record_path.as_ref().map(|path| RecordSettings {
+                path: PathBuf::from(path),
+                format: config.record_format,
+                segment_s: config.record_segment_s,
+            }),
+            calibration_offset_db: config.calibration_offset_db,
+            window_statistics: config
+                .statistics_windows_s
+                .iter()
+                .map(|&window_s| LevelStatistics::rolling(window_s))
+                .collect(),
+            ..Default::default()
+        };
+
+        if let Some(directory) = &config.capture_directory {
+            let settings = CaptureSettings {
+                directory: PathBuf::from(directory),
+                pre_s: config.capture_pre_s,
+                post_s: config.capture_post_s,
+            };
+            let capture = EventCapture::new(settings)
+                .map_err(|err| format!("Unable to create capture directory {}: {}", directory, err))?;
+            stream.capture = Some(capture);
+        }
+
+        if let Some(path) = &config.log_path {
+            let settings = LogSettings {
+                path: PathBuf::from(path),
+                format: config.log_format,
+                interval_s: config.log_interval_ms as f64 / 1000.0,
+                rotate_bytes: config.log_rotate_bytes,
+                rotate_s: config.log_rotate_s.map(|seconds| seconds as f64),
+            };
+            let logger = LevelLogger::new(settings).map_err(|err| format!("Unable to open level log {}: {}", path, err))?;
+            stream.logger = Some(logger);
+        }
+
+        if let Some(address) = &config.metrics_listen {
+            let metrics = Arc::new(Mutex::new(Metrics::default()));
+            metrics::serve(address, Arc::clone(&metrics))
+                .map_err(|err| format!("Unable to serve metrics on {}: {}", address, err))?;
+            stream.metrics = Some(metrics);
+        }
+
+        if let Some(address) = &config.live_listen {
+            let live = Arc::new(Mutex::new(None));
+            live::serve(address, Arc::clone(&live), config.live_rate_hz)
+                .map_err(|err| format!("Unable to serve live readings on {}: {}", address, err))?;
+            stream.live = Some(live);
+        }
+
+        if let Some(settings) = config.mqtt.clone() {
+            let broker = settings.broker.clone();
+            let publisher = MqttPublisher::start(settings)
+                .map_err(|err| format!("Unable to publish to MQTT broker {}: {}", broker, err))?;
+            stream.mqtt = Some(publisher);
+        }
+
+        if let Some(settings) = config.osc.clone() {
+            let target = settings.target.clone();
+            let sender = OscSender::new(settings).map_err(|err| format!("Unable to send OSC to {}: {}", target, err))?;
+            stream.osc = Some(sender);
+        }
+        Ok(stream)
+    }
+
+    /// Prepare per-channel and processor state for a source's sample layout
+    pub fn set_format(&mut self, format: SourceFormat) {
+        self.processor.configure(format);
+        self.channels = format.channels.max(1) as usize;
+        self.sample_rate = format.sample_rate;
+        self.channel_buffers = vec![Vec::new(); self.channels];
+        self.channel_levels = vec![ChannelLevel::default(); self.channels];
+        self.bars = vec![BarBallistics::new(self.ballistics); self.channels + 1];
+        self.audio_bars = vec![BarBallistics::new(self.ballistics); self.channels + 1];
+        if let Some(capture) = self.capture.as_mut() {
+            capture.set_format(format.sample_rate, self.channels);
+        }
+        // One extra state for the summed mono level
+        self.channel_states = (0..=self.channels)
+            .map(|_| ChannelState {
+                time_weighting: TimeWeightedLevel::new(self.time_weighting, format.sample_rate),
+                true_peak: TruePeakFilter::default(),
+                weighting: WeightingFilter::new(self.weighting, format.sample_rate),
+                weighted: Vec::new(),
+            })
+            .collect();
+    }
+
+    // RMS, peaks and normalized level of one channel's samples; the RMS is taken
+    // after frequency and time weighting while peaks are measured on the raw signal.
+    // The calibration offset only applies to the RMS reading, peaks stay in dBFS
+    fn measure_channel(&self, samples: &[f32], state: &mut ChannelState) -> ChannelLevel {
+        let weighted = match state.weighting.as_mut() {
+            Some(filter) => {
+                filter.process(samples, &mut state.weighted);
+                &state.weighted
+            }
+            None => samples,
+        };
+
+        let rms = match state.time_weighting.as_mut() {
+            Some(time_weighting) => time_weighting.process(weighted),
+            None => self.processor.calculate_rms(weighted),
+        };
+        let db = self.processor.calculate_db(rms);
+        let peak = self.processor.calculate_peak(samples);
+        let true_peak = self.processor.calculate_true_peak(&mut state.true_peak, samples);
+        let level = self.processor.normalize_db_to_0_100(db);
+
+        ChannelLevel {
+            db: db + self.calibration_offset_db,
+            peak_db: self.processor.calculate_db(peak),
+            true_peak_db: self.processor.calculate_db(true_peak),
+            level,
+        }
+    }
+
+    /// Run one block of interleaved samples through the metering pipeline, returning
+    /// the overall level and its dB value
+    pub fn process_block(&mut self, data: &[f32]) -> (f32, f32) {
+        if self.channels == 0 {
+            self.set_format(SourceFormat { sample_rate: 48_000, channels: 1 });
+        }
+
+        // De-interleave into one buffer per channel
+        for (channel, buffer) in self.channel_buffers.iter_mut().enumerate() {
+            buffer.clear();
+            buffer.extend(data.iter().skip(channel).step_by(self.channels));
+        }
+
+        let mut states = std::mem::take(&mut self.channel_states);
+        let mut levels = std::mem::take(&mut self.channel_levels);
+        for ((buffer, state), level) in self.channel_buffers.iter().zip(&mut states).zip(&mut levels) {
+            *level = self.measure_channel(buffer, state);
+        }
+        self.channel_levels = levels;
+
+        let loudest = self
+            .channel_levels
+            .iter()
+            .copied()
+            .fold(ChannelLevel::default(), |loudest, channel| {
+                if channel.level > loudest.level { channel } else { loudest }
+            });
+
+        self.mono_level = match self.mono_mode {
+            MonoMode::Sum => {
+                let mono: Vec<f32> = data
+                    .chunks_exact(self.channels)
+                    .map(|frame| frame.iter().sum::<f32>() / self.channels as f32)
+                    .collect();
+                self.measure_channel(&mono, &mut states[self.channels])
+            }
+            MonoMode::Max | MonoMode::Off => loudest,
+        };
+        self.channel_states = states;
+
+        // Clipping is a per-channel matter, so peaks always come from the hottest channel
+        let (peak_db, true_peak_db) = self
+            .channel_levels
+            .iter()
+            .fold((f32::NEG_INFINITY, f32::NEG_INFINITY), |(peak, true_peak), channel| {
+                (peak.max(channel.peak_db), true_peak.max(channel.true_peak_db))
+            });
+
+        // Loudness quantities replace the RMS-based mono level on the main bar
+        self.loudness = self.processor.process_program(&self.channel_buffers);
+        let program = self.loudness.as_ref().and_then(|loudness| self.meter_quantity.select(loudness));
+        if let Some(lufs) = program {
+            let db = lufs as f32;
+            self.mono_level = ChannelLevel {
+                db,
+                level: self.processor.normalize_db_to_0_100(db),
+                ..self.mono_level
+            };
+        }
+
+        let overall = self.mono_level;
+        self.update_levels(overall.level, overall.db, peak_db, true_peak_db);
+
+        // Each reading stands for the stretch of audio its block covered
+        let frames = data.len() / self.channels;
+        self.metered_frames += frames as u64;
+        let duration_s = frames as f64 / self.sample_rate as f64;
+        for (bar, levels) in self.audio_bars.iter_mut().zip(self.channel_levels.iter().chain([&overall])) {
+            bar.advance(levels.db, duration_s as f32);
+        }
+        self.statistics.add(overall.db, duration_s);
+        for window in &mut self.window_statistics {
+            window.add(overall.db, duration_s);
+        }
+        if self.mqtt.is_some() {
+            self.mqtt_statistics.add(overall.db, duration_s);
+        }
+
+        let reading = AlertReading {
+            level: overall.level,
+            db: overall.db,
+            elapsed_s: self.metered_frames as f64 / self.sample_rate as f64,
+        };
+        if let Some(capture) = self.capture.as_mut() {
+            let level = ClipLevel {
+                elapsed_s: reading.elapsed_s,
+                db: reading.db,
+                level: reading.level,
+                channels_db: self.channel_levels.iter().map(|channel| channel.db).collect(),
+            };
+            if let Err(err) = capture.push(data, level) {
+                self.output_errors.report("Unable to write alert capture", err);
+            }
+        }
+        for monitor in &mut self.alerts {
+            let value = match monitor.rule().measure {
+                AlertMeasure::Level => reading.level,
+                AlertMeasure::Db => reading.db,
+            };
+            if let Some(event) = monitor.update(value, duration_s) {
+                if let Err(err) = monitor.run_actions(event, &reading) {
+                    self.output_errors.report("Unable to run alert actions", err);
+                }
+                if let Some(mqtt) = &self.mqtt {
+                    match monitor.event_json(event, &reading) {
+                        Ok(payload) => mqtt.publish_alert(&monitor.rule().name, event.name(), payload),
+                        Err(err) => self.output_errors.report("Unable to encode MQTT alert event", err),
+                    }
+                }
+                if event == AlertEvent::Triggered {
+                    self.alerts_fired += 1;
+                    if let Some(capture) = self.capture.as_mut() {
+                        if let Err(err) = capture.trigger(monitor.rule(), &reading) {
+                            self.output_errors.report("Unable to write alert capture", err);
+                        }
+                    }
+                }
+            }
+        }
+
+        (overall.level, overall.db)
+    }
+
+    // Save the clips of alerts whose post-roll the source ended before
+    fn finish_capture(&mut self) {
+        if let Some(capture) = self.capture.as_mut() {
+            if let Err(err) = capture.finish() {
+                self.output_errors.report("Unable to write alert capture", err);
+            }
+        }
+    }
+
+    // Deliver what MQTT still has queued and announce going offline
+    fn stop_mqtt(&mut self) {
+        if let Some(mqtt) = self.mqtt.take() {
+            mqtt.finish();
+        }
+    }
+
+    /// Forget everything measured so far, as if the source had just been opened
+    pub fn reset(&mut self) {
+        self.set_format(SourceFormat { sample_rate: self.sample_rate, channels: self.channels as u16 });
+        self.mono_level = ChannelLevel::default();
+        self.loudness = None;
+        self.min_level = f32::MAX;
+        self.max_level = f32::MIN;
+        self.min_db = f32::MAX;
+        self.max_db = f32::MIN;
+        self.max_peak_db = f32::NEG_INFINITY;
+        self.max_true_peak_db = f32::NEG_INFINITY;
+        self.current_level = 0.0;
+        self.prev_level = None;
+        self.statistics.reset();
+        for window in &mut self.window_statistics {
+            window.reset();
+        }
+        for monitor in &mut self.alerts {
+            monitor.reset();
+        }
+        self.start_time = Instant::now();
+    }
+
+    fn update_levels(&mut self, level: f32, db: f32, peak_db: f32, true_peak_db: f32) {
+        // Loudness windows read -inf until they have filled, which says nothing about min/max
+        if level.is_finite() {
+            self.current_level = level;
+            if level < self.min_level {
+                self.min_level = level;
+            }
+            if level > self.max_level {
+                self.max_level = level;
+            }
+        }
+        if db.is_finite() {
+            self.min_db = self.min_db.min(db);
+            self.max_db = self.max_db.max(db);
+        }
+        if peak_db > self.max_peak_db {
+            self.max_peak_db = peak_db;
+        }
+        if true_peak_db > self.max_true_peak_db {
+            self.max_true_peak_db = true_peak_db;
+        }
+    }
+
+    /// Whether any alert rule is currently active
+    pub fn alerting(&self) -> bool {
+        self.alerts.iter().any(AlertMonitor::active)
+    }
+
+    /// Levels of each channel in the latest block
+    pub fn channel_levels(&self) -> &[ChannelLevel] {
+        &self.channel_levels
+    }
+
+    /// Statistics of the main reading since metering started
+    pub fn statistics(&self) -> &LevelStatistics {
+        &self.statistics
+    }
+
+    /// How many times an alert rule has triggered since the meter was built
+    pub fn alerts_fired(&self) -> usize {
+        self.alerts_fired
+    }
+
+    // Whether the configured duration of audio has been metered
+    fn duration_elapsed(&self) -> bool {
+        self.duration_s
+            .is_some_and(|duration_s| self.metered_frames as f64 / self.sample_rate as f64 >= duration_s - 1e-9)
+    }
+
+    /// Failed alert actions and writes of clips and the level log so far
+    pub fn output_errors(&self) -> u64 {
+        self.output_errors.count
+    }
+
+    /// Names of the active alert rules
+    pub fn active_alerts(&self) -> String {
+        let names: Vec<&str> = self
+            .alerts
+            .iter()
+            .filter(|monitor| monitor.active())
+            .map(|monitor| monitor.rule().name.as_str())
+            .collect();
+        names.join(", ")
+    }
+
+    // Write the latest readings to the level log when its interval has passed
+    fn log_levels(&mut self) {
+        let elapsed_s = self.metered_frames as f64 / self.sample_rate as f64;
+        if !self.logger.as_ref().is_some_and(|logger| logger.due(elapsed_s)) {
+            return;
+        }
+        let overall = self.mono_level;
+        let rms = self.meter_quantity == MeterQuantity::Rms;
+        let offset = if rms { self.calibration_offset_db } else { 0.0 };
+        // Smoothed in audio time, so analyzed files log the same as live input
+        let smoothed_level = self.processor.normalize_db_to_0_100(self.audio_bars[self.channels].levels().0 - offset);
+        let (min_db, max_db) = if self.min_db <= self.max_db { (self.min_db, self.max_db) } else { (f32::NAN, f32::NAN) };
+        let record = LevelRecord {
+            timestamp: logger::timestamp(SystemTime::now()),
+            elapsed_s,
+            db: overall.db,
+            dbfs: overall.db - offset,
+            level: overall.level,
+            smoothed_level,
+            min_db,
+            max_db,
+            alert: self.alerting(),
+            channels: self
+                .channel_levels
+                .iter()
+                .map(|channel| ChannelRecord { db: channel.db, level: channel.level })
+                .collect(),
+        };
+        if let Some(logger) = self.logger.as_mut() {
+            if let Err(err) = logger.write(&record) {
+                self.output_errors.report("Unable to write level log", err);
+            }
+        }
+    }
+
+    // Publish statistics of the readings since the last summary when its interval has passed
+    fn publish_mqtt_levels(&mut self) {
+        let elapsed_s = self.metered_frames as f64 / self.sample_rate as f64;
+        if !self.mqtt.as_ref().is_some_and(|mqtt| mqtt.levels_due(elapsed_s)) {
+            return;
+        }
+        let summary = self.mqtt_statistics.summary();
+        let statistic = |select: fn(&StatisticsSummary) -> f32| summary.as_ref().map_or(f32::NAN, select);
+        let levels = LevelSummary {
+            timestamp: logger::timestamp(SystemTime::now()),
+            elapsed_s,
+            duration_s: summary.as_ref().map_or(0.0, |summary| summary.duration_s as f32),
+            unit: self.unit(),
+            db: self.mono_level.db,
+            leq: statistic(|summary| summary.leq),
+            lmin: statistic(|summary| summary.lmin),
+            lmax: statistic(|summary| summary.lmax),
+            l10: statistic(|summary| summary.exceedance[0]),
+            l50: statistic(|summary| summary.exceedance[1]),
+            l90: statistic(|summary| summary.exceedance[2]),
+            l95: statistic(|summary| summary.exceedance[3]),
+            alert: self.alerting(),
+            alerts: self
+                .alerts
+                .iter()
+                .filter(|monitor| monitor.active())
+                .map(|monitor| monitor.rule().name.clone())
+                .collect(),
+            channels_db: self.channel_levels.iter().map(|channel| channel.db).collect(),
+        };
+        self.mqtt_statistics.reset();
+        if let Some(mqtt) = self.mqtt.as_mut() {
+            if let Err(err) = mqtt.publish_levels(&levels) {
+                self.output_errors.report("Unable to encode MQTT level summary", err);
+            }
+        }
+    }
+
+    // Start recording `format` if a recording is configured, before any display
+    // takes over the terminal
+    fn start_recording(&mut self, format: SourceFormat) -> Result<(), String> {
+        if let Some(settings) = self.record.clone() {
+            let path = settings.path.clone();
+            let recorder = Recorder::start(settings, format.sample_rate, format.channels.max(1) as usize)
+                .map_err(|err| format!("Unable to record to {}: {}", path.display(), err))?;
+            self.recorder = Some(recorder);
+        }
+        Ok(())
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(err) = recorder.finish() {
+                self.output_errors.report("Unable to write recording", err);
+            }
+        }
+    }
+
+    // Status segment showing the recording and its size
+    fn recording_summary(&self) -> String {
+        match &self.recorder {
+            Some(recorder) => format!(" | {}", recorder.status()),
+            None => String::new(),
+        }
+    }
+
+    // Hand the latest readings to the metrics endpoint
+    fn publish_metrics(&mut self) {
+        let Some(metrics) = self.metrics.clone() else {
+            return;
+        };
+        let overall = self.mono_level;
+        // Read from the audio-clock bars so scraping leaves the display's dynamics alone
+        let channels = self
+            .channel_levels
+            .iter()
+            .zip(&self.audio_bars)
+            .map(|(levels, bar)| ChannelMetrics { db: levels.db, smoothed_db: bar.levels().0, peak_db: levels.peak_db })
+            .collect();
+        let (min_db, max_db) = if self.min_db <= self.max_db { (self.min_db, self.max_db) } else { (f32::NAN, f32::NAN) };
+        let snapshot = Metrics {
+            db: overall.db,
+            smoothed_db: self.audio_bars[self.channels].levels().0,
+            channels,
+            min_db,
+            max_db,
+            leq: std::iter::once(&self.statistics)
+                .chain(&self.window_statistics)
+                .map(|statistics| {
+                    let window = statistics.window_s().map_or("session".to_string(), |window_s| format!("{}s", window_s));
+                    (window, statistics.summary().map_or(f32::NAN, |summary| summary.leq))
+                })
+                .collect(),
+            alerts: self
+                .alerts
+                .iter()
+                .map(|monitor| AlertMetrics {
+                    name: monitor.rule().name.clone(),
+                    active: monitor.active(),
+                    triggered: monitor.triggered(),
+                })
+                .collect(),
+            dropped_frames: self.dropped_frames,
+            stream_errors: self.stream_errors,
+            output_errors: self.output_errors.count,
+        };
+        *metrics.lock().unwrap() = snapshot;
+    }
+
+    // Hand the latest readings to the live server
+    fn publish_live(&self) {
+        let Some(live) = &self.live else {
+            return;
+        };
+        let (min_level, max_level) =
+            if self.min_level <= self.max_level { (self.min_level, self.max_level) } else { (f32::NAN, f32::NAN) };
+        let (min_db, max_db) = if self.min_db <= self.max_db { (self.min_db, self.max_db) } else { (f32::NAN, f32::NAN) };
+        let reading = LiveReading {
+            timestamp: logger::timestamp(SystemTime::now()),
+            elapsed_s: self.metered_frames as f64 / self.sample_rate as f64,
+            unit: self.unit(),
+            level: self.current_level,
+            db: self.mono_level.db,
+            min_level,
+            max_level,
+            min_db,
+            max_db,
+            trend: Trend::between(self.prev_level, self.current_level),
+            alert: self.alerting(),
+            alerts: self
+                .alerts
+                .iter()
+                .filter(|monitor| monitor.active())
+                .map(|monitor| monitor.rule().name.clone())
+                .collect(),
+            channels: self
+                .channel_levels
+                .iter()
+                .enumerate()
+                .map(|(channel, levels)| LiveChannel {
+                    label: self.channel_label(channel).trim().to_string(),
+                    level: levels.level,
+                    db: levels.db,
+                    peak_db: levels.peak_db,
+                })
+                .collect(),
+        };
+        *live.lock().unwrap() = Some(reading);
+    }
+
+    // Send the latest readings over OSC when the next bundle is due
+    fn send_osc(&mut self) {
+        let elapsed_s = self.metered_frames as f64 / self.sample_rate as f64;
+        if !self.osc.as_ref().is_some_and(|osc| osc.due(elapsed_s)) {
+            return;
+        }
+        let channel = |name: String, levels: &ChannelLevel| OscChannel {
+            name,
+            level: levels.level,
+            db: levels.db,
+            peak_level: self.processor.normalize_db_to_0_100(levels.peak_db),
+            peak_db: levels.peak_db,
+        };
+        let peak_db = self.channel_levels.iter().fold(f32::NEG_INFINITY, |peak, levels| peak.max(levels.peak_db));
+        let channels: Vec<OscChannel> = self
+            .channel_levels
+            .iter()
+            .enumerate()
+            .map(|(index, levels)| channel((index + 1).to_string(), levels))
+            .chain(std::iter::once(channel("main".to_string(), &ChannelLevel { peak_db, ..self.mono_level })))
+            .collect();
+        let alerts: Vec<(&str, bool)> = self
+            .alerts
+            .iter()
+            .map(|monitor| (monitor.rule().name.as_str(), monitor.active()))
+            .collect();
+        if let Some(osc) = self.osc.as_mut() {
+            osc.send(elapsed_s, &channels, &alerts);
+        }
+    }
+
+    // Status segment warning while the MQTT broker is unreachable
+    fn mqtt_summary(&self) -> String {
+        match &self.mqtt {
+            Some(mqtt) if !mqtt.connected() => " | MQTT offline".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // Status segment warning about audio the metering thread never saw
+    fn dropped_summary(&self) -> String {
+        if self.dropped_frames == 0 {
+            String::new()
+        } else {
+            format!(" | Dropped: {} frames", self.dropped_frames)
+        }
+    }
+
+    fn calibrated(&self) -> bool {
+        self.calibration_offset_db != 0.0
+    }
+
+    fn calculate_trend(&self) -> &str {
+        match Trend::between(self.prev_level, self.current_level) {
+            Trend::Up => "↑",
+            Trend::Down => "↓",
+            Trend::Steady => "→", // No trend or initial state
+        }
+    }
+
+    // Run bar `index` (a channel, or `channels` for the main reading) through its
+    // ballistics and return the normalized (bar, peak marker) levels
+    fn bar_levels(&mut self, index: usize, db: f32, rms: bool) -> (f32, f32) {
+        let (bar_db, peak_db) = self.bars[index].update(db, Instant::now());
+        // Calibrated RMS readings are mapped back to dBFS for the scale
+        let offset = if rms { self.calibration_offset_db } else { 0.0 };
+        (
+            self.processor.normalize_db_to_0_100(bar_db - offset),
+            self.processor.normalize_db_to_0_100(peak_db - offset),
+        )
+    }
+
+    // Colored bar of `meter_width` characters filled to `level`/100, with a
+    // peak-hold marker at `hold`/100
+    fn render_bar(&self, level: f32, hold: f32) -> String {
+        let meter_width = self.meter_width;
+        let filled_length = ((level / 100.0 * meter_width as f32).round().max(0.0) as usize).min(meter_width);
+        let marker = ((hold / 100.0 * meter_width as f32).round() as usize).clamp(1, meter_width.max(1));
+
+        let color_code = if level < 33.0 {
+            "32"  // Green for low levels
+        } else if level < 66.0 {
+            "33"  // Yellow for medium levels
+        } else {
+            "31"  // Red for high levels
+        };
+
+        let cells: String = (1..=meter_width)
+            .map(|cell| match cell {
+                _ if cell == marker && hold > 0.0 => '|',
+                _ if cell <= filled_length => '#',
+                _ => ' ',
+            })
+            .collect();
+        format!("\x1b[{}m[{}]\x1b[0m", color_code, cells)
+    }
+
+    // Unit of the per-channel RMS readings, e.g. "dB(A)" or "dB(A) SPL"
+    fn rms_unit(&self) -> String {
+        let spl = if self.calibrated() { " SPL" } else { "" };
+        format!("dB{}{}", self.weighting.suffix(), spl)
+    }
+
+    /// Unit of the main reading, e.g. "dB(A) SPL" or "LUFS"
+    pub fn unit(&self) -> String {
+        match self.meter_quantity {
+            MeterQuantity::Rms => self.rms_unit(),
+            quantity => quantity.unit().to_string(),
+        }
+    }
+
+    // Label of a statistics window, e.g. "60s" or "Session"
+    fn window_label(statistics: &LevelStatistics) -> String {
+        match statistics.window_s() {
+            Some(window_s) => format!("{}s", window_s),
+            None => "Session".to_string(),
+        }
+    }
+
+    // One-line rendering of Leq, extremes and exceedance levels
+    fn format_statistics(summary: &StatisticsSummary) -> String {
+        let exceedance: Vec<String> = EXCEEDANCE_PERCENTS
+            .iter()
+            .zip(summary.exceedance)
+            .map(|(percent, level)| format!("L{}: {:.1}", percent, level))
+            .collect();
+        format!(
+            "Leq: {:.1} | Lmin: {:.1} | Lmax: {:.1} | {}",
+            summary.leq,
+            summary.lmin,
+            summary.lmax,
+            exceedance.join(" ")
+        )
+    }
+
+    // Statistics of the session and every rolling window followed by the level
+    // histogram, printed when metering ends
+    fn print_statistics(&self) {
+        let unit = self.unit();
+        println!("  Statistics ({}):", unit);
+        for statistics in std::iter::once(&self.statistics).chain(&self.window_statistics) {
+            if let Some(summary) = statistics.summary() {
+                let label = match statistics.window_s() {
+                    Some(window_s) => format!("Last {}s", window_s),
+                    None => format!("Session ({:.1}s)", summary.duration_s),
+                };
+                println!("    {:<16} {}", label, Self::format_statistics(&summary));
+            }
+        }
+
+        let (lmin, lmax) = match self.statistics.summary() {
+            Some(summary) => (summary.lmin, summary.lmax),
+            None => {
+                println!("    No readings");
+                return;
+            }
+        };
+        // Widest useful bins that keep the histogram to a screenful
+        let width_db = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .find(|&width| (lmax - lmin) / width <= HISTOGRAM_ROWS)
+            .unwrap_or(20.0);
+        println!("  Histogram ({} dB bins):", width_db);
+        for (db, fraction) in self.statistics.histogram(width_db) {
+            println!(
+                "    {:>7.1} {} {:>5.1}% {}",
+                db,
+                unit,
+                fraction * 100.0,
+                "#".repeat((fraction * HISTOGRAM_BAR_WIDTH).round() as usize)
+            );
+        }
+    }
+
+    // Session and rolling-window statistics, one line each
+    fn statistics_lines(&self) -> Vec<String> {
+        std::iter::once(&self.statistics)
+            .chain(&self.window_statistics)
+            .filter_map(|statistics| {
+                let summary = statistics.summary()?;
+                Some(format!("{} {}", Self::window_label(statistics), Self::format_statistics(&summary)))
+            })
+            .collect()
+    }
+
+    fn channel_label(&self, channel: usize) -> String {
+        match (self.channels, channel) {
+            (2, 0) => "L  ".to_string(),
+            (2, 1) => "R  ".to_string(),
+            _ => format!("{:<3}", channel + 1),
+        }
+    }
+
+    fn display_vu_meter(&mut self, level: f32, db: f32) {
+        let alert = if self.alerting() {
+            format!(" !! ALERT: {} !! ", self.active_alerts())
+        } else {
+            String::new()
+        };
+
+        let trend = self.calculate_trend();
+        let elapsed = Instant::now().duration_since(self.start_time);
+        let elapsed_seconds = elapsed.as_secs();
+        let elapsed_millis = elapsed.subsec_millis();
+
+        let loudness = match &self.loudness {
+            Some(loudness) => format!(
+                " | M: {:.1} S: {:.1} I: {:.1} LUFS | LRA: {:.1} LU",
+                loudness.momentary, loudness.short_term, loudness.integrated, loudness.range
+            ),
+            None => String::new(),
+        };
+
+        // Once calibrated, min/max are more useful as sound pressure levels
+        let range = if self.calibrated() {
+            let unit = self.unit();
+            format!("Min: {:.2} {} | Max: {:.2} {}", self.min_db, unit, self.max_db, unit)
+        } else {
+            format!("Min: {:.2}/100 | Max: {:.2}/100", self.min_level, self.max_level)
+        };
+
+        let dropped = self.dropped_summary();
+        let recording = format!("{}{}", self.recording_summary(), self.mqtt_summary());
+        let status = format!(
+            "{} | Current: {:.2}/100 | Max peak: {:.2} dB / {:.2} dBTP{} | Trend: {} | Elapsed: {}.{:03}s{}{}{}",
+            range,
+            self.current_level,
+            self.max_peak_db,
+            self.max_true_peak_db,
+            loudness,
+            trend,
+            elapsed_seconds,
+            elapsed_millis,
+            dropped,
+            recording,
+            alert
+        );
+
+        let statistics = self.statistics_lines();
+
+        let mut lines = Vec::new();
+        if self.channels > 1 {
+            lines.push(format!("   {}", self.scale.tick_line(self.meter_width)));
+            for (channel, levels) in self.channel_levels.clone().iter().enumerate() {
+                let (bar, hold) = self.bar_levels(channel, levels.db, true);
+                lines.push(format!(
+                    "{}{} {:.2} {} | Peak: {:.2} dB | TP: {:.2} dBTP",
+                    self.channel_label(channel),
+                    self.render_bar(bar, hold),
+                    levels.db,
+                    self.rms_unit(),
+                    levels.peak_db,
+                    levels.true_peak_db
+                ));
+            }
+            let rms = self.meter_quantity == MeterQuantity::Rms;
+            if !rms {
+                let (bar, hold) = self.bar_levels(self.channels, db, rms);
+                lines.push(format!("LU {} {:.2} LUFS", self.render_bar(bar, hold), db));
+            } else if self.mono_mode != MonoMode::Off {
+                let (bar, hold) = self.bar_levels(self.channels, db, rms);
+                lines.push(format!("M  {} {:.2} {}", self.render_bar(bar, hold), db, self.unit()));
+            }
+            lines.push(status);
+            lines.extend(statistics);
+        } else {
+            let levels = self.mono_level;
+            let (bar, hold) = self.bar_levels(self.channels, db, self.meter_quantity == MeterQuantity::Rms);
+            lines.push(self.scale.tick_line(self.meter_width));
+            lines.push(format!(
+                "{} {:.2} {} | Peak: {:.2} dB | TP: {:.2} dBTP | {}",
+                self.render_bar(bar, hold),
+                db,
+                self.unit(),
+                levels.peak_db,
+                levels.true_peak_db,
+                status
+            ));
+            lines.extend(statistics);
+        }
+
+        // Move back to the first line of the previous redraw before overwriting it
+        let mut output = String::new();
+        if self.lines_drawn > 1 {
+            output.push_str(&format!("\x1b[{}A", self.lines_drawn - 1));
+        }
+        output.push('\r');
+        output.push_str(&lines.join("\x1b[K\n"));
+        output.push_str("\x1b[K");
+        self.lines_drawn = lines.len();
+
+        print!("{}", output);
+        std::io::stdout().flush().unwrap();  // Force the terminal to update
+
+        // Remember this level for the next trend
+        self.prev_level = Some(level);
+    }
+
+    // Full-screen dashboard: bars with peak-hold markers, statistics panel and
+    // the level history graph filling the rest of the terminal
+    #[cfg(unix)]
+    fn display_dashboard(&mut self, dashboard: &Dashboard, name: &str) {
+        let (columns, rows) = Terminal::size();
+        let (level, db) = (self.mono_level.level, self.mono_level.db);
+        let unit = self.unit();
+
+        let state = if dashboard.paused { "\x1b[33mPAUSED\x1b[0m" } else { "RUNNING" };
+        let alert = if self.alerting() {
+            format!("\x1b[41;97m ALERT: {} \x1b[0m", self.active_alerts())
+        } else {
+            "\x1b[42;30m  OK  \x1b[0m".to_string()
+        };
+        let recording = format!("{}{}", self.recording_summary(), self.mqtt_summary());
+        let mut lines = vec![
+            format!("db_meter | {} | {} | {} {}{}", name, unit, state, alert, recording),
+            String::new(),
+        ];
+
+        // Room for the label in front of the bar and the readings after it
+        let bar_width = columns.saturating_sub(DASHBOARD_TEXT_WIDTH).max(10);
+        lines.push(format!("    {}", self.scale.tick_line(bar_width)));
+        for (channel, levels) in self.channel_levels.clone().iter().enumerate() {
+            let (bar, hold) = self.bar_levels(channel, levels.db, true);
+            lines.push(format!(
+                "{} {} {:>7.2} {} | Pk {:>6.1} | TP {:>6.1}",
+                self.channel_label(channel),
+                Dashboard::bar(bar_width, bar / 100.0, hold / 100.0),
+                levels.db,
+                self.rms_unit(),
+                levels.peak_db,
+                levels.true_peak_db
+            ));
+        }
+        let main_label = if self.meter_quantity != MeterQuantity::Rms {
+            Some("LU ")
+        } else if self.channels > 1 && self.mono_mode != MonoMode::Off {
+            Some("M  ")
+        } else {
+            None
+        };
+        if let Some(label) = main_label {
+            let (bar, hold) = self.bar_levels(self.channels, db, self.meter_quantity == MeterQuantity::Rms);
+            let bar = Dashboard::bar(bar_width, bar / 100.0, hold / 100.0);
+            lines.push(format!("{} {} {:>7.2} {}", label, bar, db, unit));
+        }
+
+        let format_db = |value: f32| if self.max_db >= self.min_db { format!("{:.2}", value) } else { "-".to_string() };
+        lines.push(String::new());
+        lines.push(format!(
+            "Current: {:.2} {} | Min: {} | Max: {} | Trend: {}",
+            db,
+            unit,
+            format_db(self.min_db),
+            format_db(self.max_db),
+            self.calculate_trend()
+        ));
+        lines.push(format!(
+            "Max peak: {:.2} dB | True peak: {:.2} dBTP | Elapsed: {:.1}s{}",
+            self.max_peak_db,
+            self.max_true_peak_db,
+            self.start_time.elapsed().as_secs_f32(),
+            self.dropped_summary()
+        ));
+        if let Some(loudness) = &self.loudness {
+            lines.push(format!(
+                "M: {:.1} S: {:.1} I: {:.1} LUFS | LRA: {:.1} LU",
+                loudness.momentary, loudness.short_term, loudness.integrated, loudness.range
+            ));
+        }
+        lines.extend(self.statistics_lines());
+        lines.push(String::new());
+
+        let graph_height = rows.saturating_sub(lines.len() + 1);
+        if graph_height >= 3 {
+            lines.extend(dashboard.graph(&self.scale, columns, graph_height));
+        }
+        lines.push("\x1b[2mq quit | p pause | r reset\x1b[0m".to_string());
+        Dashboard::draw(&lines);
+
+        self.prev_level = Some(level);
+    }
+
+    /// Meter `source` away from its callback: the sink only queues samples, while
+    /// a metering thread processes them in `block_ms` blocks and refreshes the
+    /// front end at `refresh_hz`. Returns once the source ends or the front end
+    /// stops, or with the source's error once metering has wound down
+    pub fn run_metering<F: FrontEnd + 'static>(
+        self,
+        source: Box<dyn SampleSource>,
+        front_end: F,
+    ) -> Result<(Self, F), String> {
+        let format = source.format();
+        let live = source.is_live();
+        let errors = source.errors();
+        let mut stream = self;
+        stream.set_format(format);
+
+        let channels = stream.channels;
+        let block_len = (format.sample_rate as usize * stream.block_ms as usize / 1000).max(1) * channels;
+        let refresh_interval = Duration::from_secs_f32(1.0 / stream.refresh_hz.max(0.1));
+        let (mut producer, mut consumer) = ring_buffer(format.sample_rate as usize * channels * QUEUE_SECONDS);
+        let stop = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let meter = {
+            let (stop, finished, dropped) = (Arc::clone(&stop), Arc::clone(&finished), Arc::clone(&dropped));
+            let mut front_end = front_end;
+            thread::spawn(move || {
+                let _stop = StopOnDrop(Arc::clone(&stop));
+                let mut block = vec![0.0; block_len];
+                let mut filled = 0;
+                let mut metered = false;
+                let mut next_refresh = Instant::now();
+                loop {
+                    let done = finished.load(Ordering::Acquire);
+                    let read = consumer.pop(&mut block[filled..]);
+                    filled += read;
+
+                    // Once the source has ended a final partial block is metered too
+                    let drained = done && consumer.available() == 0;
+                    if filled == block_len || (drained && filled > 0) {
+                        let whole = filled - filled % channels;
+                        if whole > 0 {
+                            front_end.block(&mut stream, &block[..whole]);
+                            stream.log_levels();
+                            stream.publish_mqtt_levels();
+                            stream.send_osc();
+                            if let Some(recorder) = stream.recorder.as_mut() {
+                                recorder.write(&block[..whole]);
+                            }
+                            metered = true;
+                        }
+                        filled = 0;
+                    }
+
+                    // Reaching the configured duration ends metering like the source ending
+                    let expired = stream.duration_elapsed();
+                    if expired {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    let ending = (drained && filled == 0) || expired;
+                    if metered && (ending || Instant::now() >= next_refresh) {
+                        stream.dropped_frames = dropped.load(Ordering::Relaxed);
+                        stream.stream_errors = errors.as_ref().map_or(0, |errors| errors.load(Ordering::Relaxed));
+                        stream.publish_metrics();
+                        stream.publish_live();
+                        if !front_end.refresh(&mut stream) {
+                            stop.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        next_refresh = Instant::now() + refresh_interval;
+                    }
+                    if ending {
+                        break;
+                    }
+                    if read == 0 {
+                        thread::sleep(METERING_POLL);
+                    }
+                }
+                stream.finish_capture();
+                stream.stop_recording();
+                stream.stop_mqtt();
+                (stream, front_end)
+            })
+        };
+
+        let sink_stop = Arc::clone(&stop);
+        let result = source.run(Box::new(move |data: &[f32]| {
+            let mut pending = data;
+            loop {
+                let fit = producer.free().min(pending.len());
+                let written = producer.push(&pending[..fit - fit % channels]);
+                pending = &pending[written..];
+                if pending.is_empty() || sink_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                if live {
+                    // A real-time callback must never wait; count what did not fit
+                    dropped.fetch_add((pending.len() / channels) as u64, Ordering::Relaxed);
+                    break;
+                }
+                thread::sleep(METERING_POLL);
+            }
+            !sink_stop.load(Ordering::Relaxed)
+        }));
+        finished.store(true, Ordering::Release);
+
+        let metered = meter.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        result.map(|()| metered)
+    }
+
+    /// Meter a source live, redrawing the vu-meter in place until the source
+    /// ends, the configured duration has passed or Enter is pressed. Returns how
+    /// many times an alert triggered
+    pub fn run(mut self, source: Box<dyn SampleSource>) -> Result<usize, String> {
+        println!("Selected input: {}", source.name());
+        self.start_recording(source.format())?;
+
+        let enter = Arc::new(AtomicBool::new(false));
+        if !source.reads_stdin() {
+            let enter = Arc::clone(&enter);
+            thread::spawn(move || {
+                // End of input is not a request to stop
+                if let Ok(1..) = std::io::stdin().read_line(&mut String::new()) {
+                    enter.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+
+        let (stream, _) = self.run_metering(source, LineView { enter })?;
+        println!();
+        println!("Session summary");
+        stream.print_statistics();
+        Ok(stream.alerts_fired)
+    }
+
+    /// Meter a source on the full-screen dashboard until it ends, the configured
+    /// duration has passed or the user quits. Returns how many times an alert
+    /// triggered. Terminals other than Unix ones get the line display instead
+    #[cfg(unix)]
+    pub fn run_dashboard(mut self, source: Box<dyn SampleSource>) -> Result<usize, String> {
+        let name = source.name();
+        // Keys cannot be read when stdin carries the audio
+        let keyboard = !source.reads_stdin();
+        self.start_recording(source.format())?;
+
+        let terminal = Terminal::enter(keyboard);
+        let view = DashboardView {
+            dashboard: Dashboard::default(),
+            keys: keyboard.then(tui::spawn_key_reader),
+            name,
+        };
+        let metered = self.run_metering(source, view);
+        drop(terminal);
+        let (stream, _) = metered?;
+
+        println!("Session summary");
+        stream.print_statistics();
+        Ok(stream.alerts_fired)
+    }
+
+    /// Meter a source on the full-screen dashboard until it ends, the configured
+    /// duration has passed or the user quits. Returns how many times an alert
+    /// triggered. Terminals other than Unix ones get the line display instead
+    #[cfg(not(unix))]
+    pub fn run_dashboard(self, source: Box<dyn SampleSource>) -> Result<usize, String> {
+        eprintln!("The dashboard needs a Unix terminal, using the line display");
+        self.run(source)
+    }
+
+    /// Meter a source as fast as it delivers samples, up to the configured
+    /// duration, printing one report line per block followed by a summary.
+    /// Returns how many times an alert triggered
+    pub fn analyze(self, source: Box<dyn SampleSource>) -> Result<usize, String> {
+        let format = source.format();
+        println!(
+            "Analyzing {}: {} Hz, {} channel(s)",
+            source.name(),
+            format.sample_rate,
+            format.channels
+        );
+
+        let mut stream = self;
+        stream.set_format(format);
+        let report = AnalysisReport::new(stream.channels);
+        let state = Arc::new(Mutex::new((stream, report)));
+        let sink_state = Arc::clone(&state);
+        let result = source.run(Box::new(move |data: &[f32]| {
+            let (stream, report) = &mut *sink_state.lock().unwrap();
+            let offset = report.frames as f64 / format.sample_rate as f64;
+            let (final_level, db) = stream.process_block(data);
+            stream.log_levels();
+            stream.publish_mqtt_levels();
+            stream.send_osc();
+            let alert = stream.alerting();
+            report.add(data, db, alert);
+
+            let channels: String = if stream.channels > 1 {
+                let dbs: Vec<String> = stream
+                    .channel_levels
+                    .iter()
+                    .enumerate()
+                    .map(|(channel, levels)| format!("{}{:.2}", stream.channel_label(channel), levels.db))
+                    .collect();
+                format!(" | {}", dbs.join(" "))
+            } else {
+                String::new()
+            };
+
+            println!(
+                "{:>6} {:>10.3}s {:>8.2} {} | Level: {:>6.2}/100{}{}",
+                report.blocks,
+                offset,
+                db,
+                stream.unit(),
+                final_level,
+                channels,
+                if alert { " !! ALERT !! " } else { "" }
+            );
+            !stream.duration_elapsed()
+        }));
+
+        let (stream, report) = &mut *state.lock().unwrap();
+        stream.finish_capture();
+        stream.stop_mqtt();
+        result?;
+        if report.blocks == 0 {
+            println!("No audio data in source");
+            return Ok(0);
+        }
+
+        let overall_rms = |sum_of_squares: f64| (sum_of_squares / report.frames as f64).sqrt() as f32;
+        println!();
+        println!("Summary");
+        println!("  Blocks:      {}", report.blocks);
+        println!("  Duration:    {:.3}s", report.frames as f64 / format.sample_rate as f64);
+        let total: f64 = report.sum_of_squares.iter().sum::<f64>() / stream.channels as f64;
+        let unweighted = if stream.weighting == Weighting::Z { "" } else { " (unweighted)" };
+        let overall_db = |sum_of_squares: f64| {
+            stream.processor.calculate_db(overall_rms(sum_of_squares)) + stream.calibration_offset_db
+        };
+        let spl = if stream.calibrated() { " SPL" } else { "" };
+        println!("  Overall:     {:.2} dB{}{}", overall_db(total), spl, unweighted);
+        if stream.channels > 1 {
+            for (channel, &sum_of_squares) in report.sum_of_squares.iter().enumerate() {
+                println!(
+                    "    {}        {:.2} dB{}",
+                    stream.channel_label(channel),
+                    overall_db(sum_of_squares),
+                    spl
+                );
+            }
+        }
+        let unit = stream.unit();
+        // Loudness windows longer than the source never produce a reading
+        if report.loudest_db >= report.quietest_db {
+            println!("  Loudest:     {:.2} {}", report.loudest_db, unit);
+            println!("  Quietest:    {:.2} {}", report.quietest_db, unit);
+        } else {
+            println!("  Loudest:     n/a (source shorter than the measurement window)");
+        }
+        if let Some(loudness) = &stream.loudness {
+            println!("  Integrated:  {:.1} LUFS", loudness.integrated);
+            println!("  Range:       {:.1} LU", loudness.range);
+        }
+        if stream.max_level >= stream.min_level {
+            println!("  Min level:   {:.2}/100", stream.min_level);
+            println!("  Max level:   {:.2}/100", stream.max_level);
+        }
+        println!("  Max peak:    {:.2} dB", stream.max_peak_db);
+        println!("  True peak:   {:.2} dBTP", stream.max_true_peak_db);
+        println!("  Alerts:      {} block(s) with an active alert", report.alert_blocks);
+        for monitor in &stream.alerts {
+            let rule = monitor.rule();
+            let threshold = match rule.measure {
+                AlertMeasure::Level => format!("{:.2}/100", rule.threshold),
+                AlertMeasure::Db => format!("{:.2} {}", rule.threshold, unit),
+            };
+            println!("    {}: triggered {} time(s) above {}", rule.name, monitor.triggered(), threshold);
+        }
+        stream.print_statistics();
+        Ok(stream.alerts_fired)
+    }
+
+    /// Measure a calibrator tone of `reference_db` dB SPL and return the offset
+    /// that maps its dBFS reading onto that level
+    pub fn calibrate(self, source: Box<dyn SampleSource>, reference_db: f32) -> Result<f32, String> {
+        let format = source.format();
+        println!("Calibrating on {} against a {:.1} dB SPL reference", source.name(), reference_db);
+        println!("Apply the calibrator and keep it steady...");
+
+        // Plain block RMS gives the tone's equivalent level without waiting for a
+        // Slow time weighting to settle; the offset itself must not be applied
+        let mut stream = self;
+        stream.calibration_offset_db = 0.0;
+        stream.time_weighting = TimeWeighting::None;
+        // Calibration readings are not part of the level log, MQTT or OSC and raise no alerts
+        stream.logger = None;
+        stream.alerts.clear();
+        stream.capture = None;
+        stream.stop_mqtt();
+        stream.osc = None;
+
+        // Give the calibrator and the input a moment to settle before averaging
+        let settle_frames = (CALIBRATION_SETTLE_S * format.sample_rate as f32) as u64;
+        let view = CalibrationView {
+            sample_rate: format.sample_rate,
+            frames: 0,
+            settle_frames,
+            total_frames: settle_frames + (CALIBRATION_MEASURE_S * format.sample_rate as f32) as u64,
+            readings: Vec::new(),
+        };
+        let (_, CalibrationView { readings, .. }) = stream.run_metering(source, view)?;
+        println!();
+
+        let finite: Vec<f32> = readings.iter().copied().filter(|db| db.is_finite()).collect();
+        if finite.is_empty() {
+            return Err("Calibration failed: no audio was measured".to_string());
+        }
+        let mean_power = finite.iter().map(|&db| 10f64.powf(db as f64 / 10.0)).sum::<f64>() / finite.len() as f64;
+        let measured = (10.0 * mean_power.log10()) as f32;
+        if measured < CALIBRATION_MIN_DBFS {
+            return Err(format!("Calibration failed: signal too quiet ({:.2} dBFS)", measured));
+        }
+
+        let (quietest, loudest) = finite
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &db| (min.min(db), max.max(db)));
+        if loudest - quietest > CALIBRATION_MAX_SPREAD_DB {
+            eprintln!(
+                "Warning: level varied by {:.2} dB during calibration, the tone may not have been steady",
+                loudest - quietest
+            );
+        }
+
+        println!("Measured {:.2} dBFS", measured);
+        Ok(((reference_db - measured) * 100.0).round() / 100.0)
+    }
+}
+
+// Sets the flag when dropped, so the sink stops feeding a queue nobody drains
+// however the metering thread ends, a panic included
+struct StopOnDrop(Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Receives metered audio on the metering thread, away from the audio callback
+pub trait FrontEnd: Send {
+    /// Called with every block of interleaved samples taken off the queue
+    fn block(&mut self, stream: &mut AudioStream, data: &[f32]) {
+        stream.process_block(data);
+    }
+
+    /// Called at the refresh rate and once more when the source ends; returning
+    /// false stops metering
+    fn refresh(&mut self, stream: &mut AudioStream) -> bool;
+}
+
+// Single-line (or one line per channel) meter redrawn in place
+struct LineView {
+    enter: Arc<AtomicBool>,
+}
+
+impl FrontEnd for LineView {
+    fn refresh(&mut self, stream: &mut AudioStream) -> bool {
+        let overall = stream.mono_level;
+        stream.display_vu_meter(overall.level, overall.db);
+        !self.enter.load(Ordering::Relaxed)
+    }
+}
+
+// Full-screen dashboard driven by the keyboard
+#[cfg(unix)]
+struct DashboardView {
+    dashboard: Dashboard,
+    keys: Option<std::sync::mpsc::Receiver<Key>>,
+    name: String,
+}
+
+#[cfg(unix)]
+impl FrontEnd for DashboardView {
+    fn block(&mut self, stream: &mut AudioStream, data: &[f32]) {
+        if !self.dashboard.paused {
+            let (level, _) = stream.process_block(data);
+            self.dashboard.push_history(level / 100.0, stream.alerting());
+        }
+    }
+
+    fn refresh(&mut self, stream: &mut AudioStream) -> bool {
+        for key in self.keys.iter().flat_map(|keys| keys.try_iter()) {
+            match key {
+                Key::Quit => return false,
+                Key::Pause => self.dashboard.paused = !self.dashboard.paused,
+                Key::Reset => {
+                    stream.reset();
+                    self.dashboard.reset();
+                }
+            }
+        }
+        stream.display_dashboard(&self.dashboard, &self.name);
+        true
+    }
+}
+
+// Collects block readings of a calibrator tone after a settling period
+struct CalibrationView {
+    sample_rate: u32,
+    frames: u64,
+    settle_frames: u64,
+    total_frames: u64,
+    readings: Vec<f32>,
+}
+
+impl FrontEnd for CalibrationView {
+    fn block(&mut self, stream: &mut AudioStream, data: &[f32]) {
+        let (_, db) = stream.process_block(data);
+        self.frames += (data.len() / stream.channels) as u64;
+        if self.frames > self.settle_frames && self.frames <= self.total_frames {
+            self.readings.push(db);
+        }
+    }
+
+    fn refresh(&mut self, stream: &mut AudioStream) -> bool {
+        if self.frames > self.settle_frames {
+            print!(
+                "\rMeasuring: {:.2} dBFS ({:.1}s)\x1b[K",
+                stream.mono_level.db,
+                (self.frames.min(self.total_frames) - self.settle_frames) as f32 / self.sample_rate as f32
+            );
+            std::io::stdout().flush().unwrap();
+        }
+        self.frames < self.total_frames
+    }
+}
+
+// Seconds of audio the queue between the audio callback and the metering thread holds
+const QUEUE_SECONDS: usize = 2;
+// How long the metering thread and a non-real-time source wait for the queue
+const METERING_POLL: Duration = Duration::from_millis(2);
+
+// Columns taken by the channel label and the readings next to a dashboard bar
+#[cfg(unix)]
+const DASHBOARD_TEXT_WIDTH: usize = 52;
+
+const HISTOGRAM_ROWS: f32 = 30.0;
+const HISTOGRAM_BAR_WIDTH: f64 = 50.0;
+
+const CALIBRATION_SETTLE_S: f32 = 0.5;
+const CALIBRATION_MEASURE_S: f32 = 5.0;
+const CALIBRATION_MIN_DBFS: f32 = -90.0;
+const CALIBRATION_MAX_SPREAD_DB: f32 = 1.0;
+
+// Totals accumulated over an analysis run
+struct AnalysisReport {
+    blocks: usize,
+    alert_blocks: usize,
+    frames: u64,
+    sum_of_squares: Vec<f64>, // Per channel
+    loudest_db: f32,
+    quietest_db: f32,
+}
+
+impl AnalysisReport {
+    fn new(channels: usize) -> Self {
+        Self {
+            blocks: 0,
+            alert_blocks: 0,
+            frames: 0,
+            sum_of_squares: vec![0.0; channels],
+            loudest_db: f32::MIN,
+            quietest_db: f32::MAX,
+        }
+    }
+
+    fn add(&mut self, data: &[f32], db: f32, alert: bool) {
+        let channels = self.sum_of_squares.len();
+        self.blocks += 1;
+        if alert {
+            self.alert_blocks += 1;
+        }
+        self.frames += (data.len() / channels) as u64;
+        for (i, &sample) in data.iter().enumerate() {
+            self.sum_of_squares[i % channels] += (sample as f64) * (sample as f64);
+        }
+        if db.is_finite() {
+            self.loudest_db = self.loudest_db.max(db);
+            self.quietest_db = self.quietest_db.min(db);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::AlertRule;
+    use crate::osc::OscSettings;
+
+    // Interleaved stereo block with a 1 kHz sine of `amplitude` on the left
+    // channel and silence on the right
+    fn stereo_block(amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|n| [amplitude * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48_000.0).sin(), 0.0])
+            .collect()
+    }
+
+    #[test]
+    fn measures_each_channel_and_the_main_reading() {
+        let config = Config { time_weighting: TimeWeighting::None, ..Config::default() };
+        let mut stream = AudioStream::from_config(&config).unwrap();
+        stream.set_format(SourceFormat { sample_rate: 48_000, channels: 2 });
+
+        let (level, db) = stream.process_block(&stereo_block(1.0, 4800));
+        assert!((db + 3.01).abs() < 0.05, "{}", db);
+        assert!((level - 96.99).abs() < 0.1, "{}", level);
+
+        let channels = stream.channel_levels();
+        assert_eq!(channels.len(), 2);
+        assert!(channels[0].peak_db.abs() < 0.01);
+        assert!(channels[1].db < -150.0);
+        assert_eq!(stream.unit(), "dB");
+        assert!(stream.statistics().summary().is_some());
+    }
+
+    #[test]
+    fn renders_bars_of_any_width() {
+        let mut stream = AudioStream { meter_width: 4, ..AudioStream::default() };
+        assert_eq!(stream.render_bar(50.0, 100.0), "\x1b[33m[## |]\x1b[0m");
+        stream.meter_width = 0;
+        assert_eq!(stream.render_bar(50.0, 100.0), "\x1b[33m[]\x1b[0m");
+    }
+
+    #[test]
+    fn deinterleaves_each_channel() {
+        let config = Config { time_weighting: TimeWeighting::None, ..Config::default() };
+        let mut stream = AudioStream::from_config(&config).unwrap();
+        stream.set_format(SourceFormat { sample_rate: 48_000, channels: 3 });
+
+        // Constant levels of full scale, half and a quarter on channels 1 to 3
+        let block: Vec<f32> = (0..4800).flat_map(|_| [1.0, -0.5, 0.25]).collect();
+        stream.process_block(&block);
+        let channels = stream.channel_levels();
+        assert_eq!(channels.len(), 3);
+        for (channel, expected) in channels.iter().zip([0.0, -6.02, -12.04]) {
+            assert!((channel.db - expected).abs() < 0.05, "{} vs {}", channel.db, expected);
+        }
+    }
+
+    #[test]
+    fn mono_sums_or_takes_the_loudest_channel() {
+        let reading = |mono_mode, block: &[f32]| {
+            let config = Config { time_weighting: TimeWeighting::None, mono_mode, ..Config::default() };
+            let mut stream = AudioStream::from_config(&config).unwrap();
+            stream.set_format(SourceFormat { sample_rate: 48_000, channels: 2 });
+            stream.process_block(block).1
+        };
+        let left_only = stereo_block(1.0, 4800);
+        let both: Vec<f32> = left_only.chunks_exact(2).flat_map(|frame| [frame[0], frame[0]]).collect();
+
+        // Summing averages the channels, so identical ones read as either alone
+        assert!((reading(MonoMode::Sum, &both) - reading(MonoMode::Max, &both)).abs() < 0.01);
+        // A silent channel halves the sum
+        let sum = reading(MonoMode::Sum, &left_only);
+        assert!((sum - (reading(MonoMode::Max, &left_only) - 6.02)).abs() < 0.05, "{}", sum);
+        // Max follows the loudest channel wherever it is
+        let right_only: Vec<f32> = left_only.chunks_exact(2).flat_map(|frame| [0.0, frame[0]]).collect();
+        assert!((reading(MonoMode::Max, &right_only) - reading(MonoMode::Max, &left_only)).abs() < 0.01);
+    }
+
+    #[test]
+    fn log_smoothing_follows_audio_time() {
+        let config = Config { time_weighting: TimeWeighting::None, ..Config::default() };
+        let mut stream = AudioStream::from_config(&config).unwrap();
+        stream.set_format(SourceFormat { sample_rate: 48_000, channels: 2 });
+
+        // Processed far faster than real time, as when analyzing a file
+        let (_, loud) = stream.process_block(&stereo_block(1.0, 4800));
+        let mut quiet = 0.0;
+        for _ in 0..3 {
+            quiet = stream.process_block(&stereo_block(0.1, 4800)).1;
+        }
+        // One release time constant of audio later the bar has fallen 63% of the way
+        let expected = quiet + (loud - quiet) * (-1.0f32).exp();
+        let smoothed = stream.audio_bars[2].levels().0;
+        assert!((smoothed - expected).abs() < 0.1, "{} vs {}", smoothed, expected);
+    }
+
+    #[test]
+    fn metrics_leave_the_display_bars_alone() {
+        let config = Config { time_weighting: TimeWeighting::None, ..Config::default() };
+        let mut stream = AudioStream::from_config(&config).unwrap();
+        stream.metrics = Some(Arc::new(Mutex::new(Metrics::default())));
+        stream.set_format(SourceFormat { sample_rate: 48_000, channels: 2 });
+        stream.process_block(&stereo_block(1.0, 4800));
+
+        let display = stream.bars[2].levels();
+        stream.publish_metrics();
+        stream.publish_metrics();
+        assert_eq!(stream.bars[2].levels(), display);
+        let smoothed_db = stream.metrics.as_ref().unwrap().lock().unwrap().smoothed_db;
+        assert_eq!(smoothed_db, stream.audio_bars[2].levels().0);
+    }
+
+    #[test]
+    fn alerts_follow_the_reading_until_reset() {
+        let config = Config { time_weighting: TimeWeighting::None, alert_threshold_db: Some(-10.0), ..Config::default() };
+        let mut stream = AudioStream::from_config(&config).unwrap();
+        stream.set_format(SourceFormat { sample_rate: 48_000, channels: 2 });
+
+        stream.process_block(&stereo_block(0.1, 4800));
+        assert!(!stream.alerting());
+        stream.process_block(&stereo_block(1.0, 4800));
+        assert!(stream.alerting());
+        assert_eq!(stream.active_alerts(), "threshold");
+
+        stream.reset();
+        assert!(!stream.alerting());
+        assert!(stream.statistics().summary().is_none());
+        assert_eq!(stream.alerts_fired(), 1);
+    }
+
+    #[test]
+    fn failing_alert_actions_are_counted_not_fatal() {
+        let rule = AlertRule {
+            measure: AlertMeasure::Db,
+            threshold: -10.0,
+            event_log: Some("/nonexistent/db_meter/events.jsonl".to_string()),
+            ..AlertRule::default()
+        };
+        let config = Config { time_weighting: TimeWeighting::None, alert_rules: vec![rule], ..Config::default() };
+        let mut stream = AudioStream::from_config(&config).unwrap();
+        stream.set_format(SourceFormat { sample_rate: 48_000, channels: 2 });
+
+        stream.process_block(&stereo_block(1.0, 4800));
+        stream.process_block(&stereo_block(0.1, 4800));
+        stream.process_block(&stereo_block(1.0, 4800));
+        assert_eq!(stream.output_errors(), 3);
+        assert_eq!(stream.alerts_fired(), 2);
+    }
+
+    // Endless silence, delivered as fast as the sink takes it
+    struct Endless;
+
+    impl SampleSource for Endless {
+        fn name(&self) -> String {
+            "endless".to_string()
+        }
+
+        fn format(&self) -> SourceFormat {
+            SourceFormat { sample_rate: 8000, channels: 1 }
+        }
+
+        fn run(self: Box<Self>, mut sink: crate::source::BlockSink) -> Result<(), String> {
+            while sink(&[0.0; 800]) {}
+            Ok(())
+        }
+    }
+
+    // 1 kHz sine of `amplitude` at 8 kHz, delivered as fast as the sink takes it
+    struct Tone(f32);
+
+    impl SampleSource for Tone {
+        fn name(&self) -> String {
+            "tone".to_string()
+        }
+
+        fn format(&self) -> SourceFormat {
+            SourceFormat { sample_rate: 8000, channels: 1 }
+        }
+
+        fn run(self: Box<Self>, mut sink: crate::source::BlockSink) -> Result<(), String> {
+            // Whole periods, so repeating the block keeps the phase continuous
+            let block: Vec<f32> =
+                (0..800).map(|n| self.0 * (2.0 * std::f32::consts::PI * n as f32 / 8.0).sin()).collect();
+            while sink(&block) {}
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn calibration_maps_the_tone_onto_the_reference() {
+        let config = Config { calibration_offset_db: 20.0, ..Config::default() };
+        // A sine of amplitude 0.5 has an RMS of -9.03 dBFS; the old offset plays no part
+        let offset = AudioStream::from_config(&config).unwrap().calibrate(Box::new(Tone(0.5)), 94.0).unwrap();
+        assert!((offset - 103.03).abs() < 0.02, "{}", offset);
+
+        let err = AudioStream::from_config(&config).unwrap().calibrate(Box::new(Endless), 94.0).unwrap_err();
+        assert!(err.starts_with("Calibration failed"), "{}", err);
+    }
+
+    struct Crashing;
+
+    impl FrontEnd for Crashing {
+        fn block(&mut self, _stream: &mut AudioStream, _data: &[f32]) {
+            panic!("front end failed");
+        }
+
+        fn refresh(&mut self, _stream: &mut AudioStream) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn source_stops_when_the_metering_thread_dies() {
+        let stream = AudioStream::default();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = stream.run_metering(Box::new(Endless), Crashing);
+        }));
+        assert!(result.is_err());
+    }
+
+    // Fails after delivering one block
+    struct Failing;
+
+    impl SampleSource for Failing {
+        fn name(&self) -> String {
+            "failing".to_string()
+        }
+
+        fn format(&self) -> SourceFormat {
+            SourceFormat { sample_rate: 8000, channels: 1 }
+        }
+
+        fn run(self: Box<Self>, mut sink: crate::source::BlockSink) -> Result<(), String> {
+            sink(&[0.5; 800]);
+            Err("device unplugged".to_string())
+        }
+    }
+
+    #[test]
+    fn errors_are_returned_not_fatal() {
+        let config = Config { record_path: Some("/nonexistent/db_meter/take.wav".to_string()), ..Config::default() };
+        let stream = AudioStream::from_config(&config).unwrap();
+        let err = stream.run(Box::new(Endless)).unwrap_err();
+        assert!(err.starts_with("Unable to record to /nonexistent/db_meter/take.wav"), "{}", err);
+
+        let stream = AudioStream::from_config(&Config::default()).unwrap();
+        assert_eq!(stream.analyze(Box::new(Failing)).unwrap_err(), "device unplugged");
+    }
+
+    #[test]
+    fn stops_after_the_configured_duration() {
+        let config = Config { duration_s: Some(0.2), ..Config::default() };
+        let mut stream = AudioStream::from_config(&config).unwrap();
+        stream.set_format(SourceFormat { sample_rate: 48_000, channels: 2 });
+        stream.process_block(&stereo_block(0.5, 4800));
+        assert!(!stream.duration_elapsed());
+        stream.process_block(&stereo_block(0.5, 4800));
+        assert!(stream.duration_elapsed());
+    }
+
+    #[test]
+    fn reports_outputs_that_cannot_start() {
+        let osc = OscSettings { target: "not an address".to_string(), ..OscSettings::default() };
+        let config = Config { osc: Some(osc), ..Config::default() };
+        let err = AudioStream::from_config(&config).err().unwrap();
+        assert!(err.starts_with("Unable to send OSC to not an address"), "{}", err);
+    }
+}
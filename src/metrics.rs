@@ -0,0 +1,260 @@
+use std::fmt::Write as _;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::http::{self, Request};
+
+/// Readings of one channel
+#[derive(Debug, Clone)]
+pub struct ChannelMetrics {
+    /// RMS reading
+    pub db: f32,
+    /// RMS reading after the bar ballistics
+    pub smoothed_db: f32,
+    /// Sample peak in dBFS
+    pub peak_db: f32,
+}
+
+/// State of one alert rule
+#[derive(Debug, Clone)]
+pub struct AlertMetrics {
+    /// Rule name
+    pub name: String,
+    /// Whether the rule is currently active
+    pub active: bool,
+    /// Times the rule has triggered
+    pub triggered: usize,
+}
+
+/// Latest readings published by the metering thread for scraping. Levels are in
+/// the meter's unit, dB SPL once calibrated
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    /// Main reading
+    pub db: f32,
+    /// Main reading after the bar ballistics
+    pub smoothed_db: f32,
+    /// Readings of each channel
+    pub channels: Vec<ChannelMetrics>,
+    /// Lowest reading so far
+    pub min_db: f32,
+    /// Highest reading so far
+    pub max_db: f32,
+    /// (window, Leq) for the session and each rolling window
+    pub leq: Vec<(String, f32)>,
+    /// State of each alert rule
+    pub alerts: Vec<AlertMetrics>,
+    /// Frames the audio callback could not queue
+    pub dropped_frames: u64,
+    /// Errors the input stream reported
+    pub stream_errors: u64,
+    /// Failed alert actions, clip and level log writes
+    pub output_errors: u64,
+}
+
+// Nothing measured yet
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            db: f32::NAN,
+            smoothed_db: f32::NAN,
+            channels: Vec::new(),
+            min_db: f32::NAN,
+            max_db: f32::NAN,
+            leq: Vec::new(),
+            alerts: Vec::new(),
+            dropped_frames: 0,
+            stream_errors: 0,
+            output_errors: 0,
+        }
+    }
+}
+
+// Gauge value in the exposition format's spelling
+fn value(value: f32) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Writes metric families in the Prometheus text format
+struct Exposition(String);
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], sample: &str) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, label)| format!("{}=\"{}\"", key, escape_label(label)))
+            .collect();
+        if labels.is_empty() {
+            let _ = writeln!(self.0, "{} {}", name, sample);
+        } else {
+            let _ = writeln!(self.0, "{}{{{}}} {}", name, labels.join(","), sample);
+        }
+    }
+
+    fn gauge(&mut self, name: &str, labels: &[(&str, &str)], gauge: f32) {
+        self.sample(name, labels, &value(gauge));
+    }
+
+    fn counter(&mut self, name: &str, labels: &[(&str, &str)], counter: u64) {
+        self.sample(name, labels, &counter.to_string());
+    }
+}
+
+impl Metrics {
+    /// Everything in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = Exposition(String::new());
+
+        out.family("db_meter_level_db", "gauge", "Current main reading in dB.");
+        out.gauge("db_meter_level_db", &[], self.db);
+        out.family("db_meter_smoothed_level_db", "gauge", "Main reading after the display ballistics in dB.");
+        out.gauge("db_meter_smoothed_level_db", &[], self.smoothed_db);
+
+        let channels: Vec<String> = (1..=self.channels.len()).map(|channel| channel.to_string()).collect();
+        out.family("db_meter_channel_level_db", "gauge", "Current reading per channel in dB.");
+        for (label, channel) in channels.iter().zip(&self.channels) {
+            out.gauge("db_meter_channel_level_db", &[("channel", label)], channel.db);
+        }
+        out.family("db_meter_channel_smoothed_level_db", "gauge", "Reading per channel after the display ballistics in dB.");
+        for (label, channel) in channels.iter().zip(&self.channels) {
+            out.gauge("db_meter_channel_smoothed_level_db", &[("channel", label)], channel.smoothed_db);
+        }
+        out.family("db_meter_channel_peak_db", "gauge", "Sample peak of the latest block per channel in dBFS.");
+        for (label, channel) in channels.iter().zip(&self.channels) {
+            out.gauge("db_meter_channel_peak_db", &[("channel", label)], channel.peak_db);
+        }
+
+        out.family("db_meter_min_level_db", "gauge", "Lowest main reading since the start in dB.");
+        out.gauge("db_meter_min_level_db", &[], self.min_db);
+        out.family("db_meter_max_level_db", "gauge", "Highest main reading since the start in dB.");
+        out.gauge("db_meter_max_level_db", &[], self.max_db);
+
+        out.family("db_meter_leq_db", "gauge", "Equivalent continuous level over the session or a rolling window in dB.");
+        for (window, leq) in &self.leq {
+            out.gauge("db_meter_leq_db", &[("window", window)], *leq);
+        }
+
+        out.family("db_meter_alert_active", "gauge", "Whether an alert rule is currently active.");
+        for alert in &self.alerts {
+            out.gauge("db_meter_alert_active", &[("rule", &alert.name)], alert.active as u8 as f32);
+        }
+        out.family("db_meter_alerts_total", "counter", "Times an alert rule has triggered.");
+        for alert in &self.alerts {
+            out.counter("db_meter_alerts_total", &[("rule", &alert.name)], alert.triggered as u64);
+        }
+
+        out.family("db_meter_dropped_frames_total", "counter", "Frames the audio callback could not queue.");
+        out.counter("db_meter_dropped_frames_total", &[], self.dropped_frames);
+        out.family("db_meter_stream_errors_total", "counter", "Errors reported by the input stream.");
+        out.counter("db_meter_stream_errors_total", &[], self.stream_errors);
+        out.family("db_meter_output_errors_total", "counter", "Failed alert actions and writes of clips and the level log.");
+        out.counter("db_meter_output_errors_total", &[], self.output_errors);
+        out.0
+    }
+}
+
+/// Serves `GET /metrics` from a background thread; returns the bound address,
+/// which tells the actual port when binding to port 0
+pub fn serve(address: &str, metrics: Arc<Mutex<Metrics>>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local = listener.local_addr()?;
+    thread::spawn(move || {
+        for client in listener.incoming().flatten() {
+            // One scraper at a time is all this needs; a broken client only loses its response
+            let _ = respond(client, &metrics);
+        }
+    });
+    Ok(local)
+}
+
+fn respond(mut client: TcpStream, metrics: &Mutex<Metrics>) -> io::Result<()> {
+    let request = Request::read(&mut client)?;
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/metrics") => {
+            let body = metrics.lock().unwrap().render();
+            http::respond(&mut client, "200 OK", "text/plain; version=0.0.4; charset=utf-8", &body)
+        }
+        ("GET", _) => http::not_found(&mut client),
+        _ => http::method_not_allowed(&mut client),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    fn metrics() -> Metrics {
+        Metrics {
+            db: -20.5,
+            smoothed_db: -21.0,
+            channels: vec![
+                ChannelMetrics { db: -20.5, smoothed_db: -21.0, peak_db: -12.0 },
+                ChannelMetrics { db: f32::NEG_INFINITY, smoothed_db: -200.0, peak_db: f32::NEG_INFINITY },
+            ],
+            min_db: -40.0,
+            max_db: -10.0,
+            leq: vec![("session".to_string(), -22.25), ("60s".to_string(), -23.5)],
+            alerts: vec![AlertMetrics { name: "loud \"hall\"".to_string(), active: true, triggered: 3 }],
+            dropped_frames: 7,
+            stream_errors: 2,
+            output_errors: 1,
+        }
+    }
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut client = TcpStream::connect(address).unwrap();
+        write!(client, "GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn renders_exposition_format() {
+        let text = metrics().render();
+        assert!(text.contains("# TYPE db_meter_level_db gauge\ndb_meter_level_db -20.5\n"));
+        assert!(text.contains("db_meter_channel_level_db{channel=\"2\"} -Inf\n"));
+        assert!(text.contains("db_meter_leq_db{window=\"60s\"} -23.5\n"));
+        assert!(text.contains("db_meter_alert_active{rule=\"loud \\\"hall\\\"\"} 1\n"));
+        assert!(text.contains("# TYPE db_meter_alerts_total counter\ndb_meter_alerts_total{rule=\"loud \\\"hall\\\"\"} 3\n"));
+        assert!(text.contains("db_meter_stream_errors_total 2\n"));
+        assert!(text.contains("db_meter_output_errors_total 1\n"));
+        assert!(Metrics::default().render().contains("db_meter_level_db NaN\n"));
+    }
+
+    #[test]
+    fn serves_metrics_over_http() {
+        let shared = Arc::new(Mutex::new(metrics()));
+        let address = serve("127.0.0.1:0", Arc::clone(&shared)).unwrap();
+
+        let response = get(address, "/metrics");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert!(body.contains("db_meter_dropped_frames_total 7\n"));
+
+        // Later scrapes see what the metering thread published since
+        shared.lock().unwrap().stream_errors = 5;
+        assert!(get(address, "/metrics?x=1").contains("db_meter_stream_errors_total 5\n"));
+
+        assert!(get(address, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
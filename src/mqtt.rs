@@ -0,0 +1,548 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+// Messages waiting for the broker beyond this are dropped rather than held in memory
+const QUEUE_LEN: usize = 1000;
+// Longest wait for the broker to accept a connection or acknowledge a packet
+const BROKER_TIMEOUT: Duration = Duration::from_secs(10);
+// First wait before reconnecting, doubled after every failed attempt
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+// Packet types (MQTT 3.1.1 section 2.2.1), shifted into the fixed header
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const PUBREC: u8 = 0x50;
+const PUBREL: u8 = 0x62; // Carries the reserved flags the spec requires
+const PUBCOMP: u8 = 0x70;
+const PINGREQ: u8 = 0xC0;
+const PINGRESP: u8 = 0xD0;
+const DISCONNECT: u8 = 0xE0;
+
+/// Broker, topics and delivery options, as written in config.json. Topics may
+/// contain {client_id}; the alert topic also {rule} and {event}
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MqttSettings {
+    /// Broker as host:port
+    pub broker: String,
+    /// Client identifier, also filled into the topics
+    pub client_id: String,
+    /// User name to authenticate with
+    pub username: Option<String>,
+    /// Password to authenticate with, only sent together with a user name
+    pub password: Option<String>,
+    /// Seconds between pings while idle
+    pub keep_alive_s: u16,
+    /// Quality of service, 0, 1 or 2, for everything published
+    pub qos: u8,
+    /// Topic of the level summaries
+    pub level_topic: String,
+    /// Audio time summarized by each level message
+    pub level_interval_s: f64,
+    /// Publish level summaries as retained messages
+    pub retain_levels: bool,
+    /// Topic of alert events
+    pub alert_topic: String,
+    /// Publish alert events as retained messages
+    pub retain_alerts: bool,
+    /// Topic of the retained "online", and of "offline" on exit or as the last will
+    pub status_topic: String,
+    /// Longest wait between reconnection attempts
+    pub reconnect_max_s: f32,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            broker: "localhost:1883".to_string(),
+            client_id: "db_meter".to_string(),
+            username: None,
+            password: None,
+            keep_alive_s: 30,
+            qos: 0,
+            level_topic: "db_meter/{client_id}/levels".to_string(),
+            level_interval_s: 10.0,
+            retain_levels: false,
+            alert_topic: "db_meter/{client_id}/alerts/{rule}".to_string(),
+            retain_alerts: false,
+            status_topic: "db_meter/{client_id}/status".to_string(),
+            reconnect_max_s: 60.0,
+        }
+    }
+}
+
+/// Statistics of one level interval, published as JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelSummary {
+    /// Wall-clock time in UTC, RFC 3339
+    pub timestamp: String,
+    /// Audio time at the end of the interval
+    pub elapsed_s: f64,
+    /// Audio the statistics cover
+    pub duration_s: f32,
+    /// Unit of the readings, e.g. "dB(A) SPL"
+    pub unit: String,
+    /// Latest reading
+    pub db: f32,
+    /// Equivalent continuous level
+    pub leq: f32,
+    /// Lowest reading
+    pub lmin: f32,
+    /// Highest reading
+    pub lmax: f32,
+    /// Level exceeded 10% of the time
+    pub l10: f32,
+    /// Level exceeded 50% of the time
+    pub l50: f32,
+    /// Level exceeded 90% of the time
+    pub l90: f32,
+    /// Level exceeded 95% of the time
+    pub l95: f32,
+    /// Whether any alert rule is active
+    pub alert: bool,
+    /// Names of the active alert rules
+    pub alerts: Vec<String>,
+    /// Latest reading of each channel
+    pub channels_db: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+struct Message {
+    topic: String,
+    payload: Vec<u8>,
+    retain: bool,
+}
+
+/// Fill `{name}` placeholders into a topic template. Values cannot add topic
+/// levels or wildcards
+pub fn render_topic(template: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(template.to_string(), |topic, (name, value)| {
+        topic.replace(&format!("{{{}}}", name), &value.replace(['/', '+', '#'], "_"))
+    })
+}
+
+fn push_length(packet: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+}
+
+fn push_string(body: &mut Vec<u8>, value: &[u8]) {
+    body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    body.extend_from_slice(value);
+}
+
+// Fixed header with the remaining length, followed by `body`
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    push_length(&mut packet, body.len());
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn connect_packet(settings: &MqttSettings, status_topic: &str) -> Vec<u8> {
+    // Clean session with a retained will at the configured QoS
+    let mut flags = 0x02 | 0x04 | 0x20 | (settings.qos << 3);
+    // MQTT 3.1.1 only allows a password after a user name
+    let password = settings.password.as_ref().filter(|_| settings.username.is_some());
+    if password.is_some() {
+        flags |= 0x40;
+    }
+    if settings.username.is_some() {
+        flags |= 0x80;
+    }
+    let mut body = Vec::new();
+    push_string(&mut body, b"MQTT");
+    body.push(4); // Protocol level of 3.1.1
+    body.push(flags);
+    body.extend_from_slice(&settings.keep_alive_s.to_be_bytes());
+    push_string(&mut body, settings.client_id.as_bytes());
+    push_string(&mut body, status_topic.as_bytes());
+    push_string(&mut body, b"offline");
+    for value in [settings.username.as_ref(), password].into_iter().flatten() {
+        push_string(&mut body, value.as_bytes());
+    }
+    packet(CONNECT, &body)
+}
+
+fn publish_packet(message: &Message, qos: u8, packet_id: u16) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, message.topic.as_bytes());
+    if qos > 0 {
+        body.extend_from_slice(&packet_id.to_be_bytes());
+    }
+    body.extend_from_slice(&message.payload);
+    packet(PUBLISH | (qos << 1) | message.retain as u8, &body)
+}
+
+// Read one packet, returning its fixed header byte and body
+fn read_packet(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut byte = [0u8; 1];
+    stream.read_exact(&mut byte)?;
+    let header = byte[0];
+    let mut length = 0usize;
+    for shift in 0..4 {
+        stream.read_exact(&mut byte)?;
+        length |= ((byte[0] & 0x7F) as usize) << (7 * shift);
+        if byte[0] & 0x80 == 0 {
+            let mut body = vec![0u8; length];
+            stream.read_exact(&mut body)?;
+            return Ok((header, body));
+        }
+    }
+    Err(io::Error::new(ErrorKind::InvalidData, "malformed MQTT remaining length"))
+}
+
+// An open session with the broker
+struct Connection {
+    stream: TcpStream,
+    qos: u8,
+    status_topic: String,
+    next_id: u16,
+}
+
+impl Connection {
+    fn open(settings: &MqttSettings, status_topic: &str) -> io::Result<Self> {
+        let address = settings
+            .broker
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "broker address did not resolve"))?;
+        let mut stream = TcpStream::connect_timeout(&address, BROKER_TIMEOUT)?;
+        stream.set_read_timeout(Some(BROKER_TIMEOUT))?;
+        stream.set_write_timeout(Some(BROKER_TIMEOUT))?;
+        stream.write_all(&connect_packet(settings, status_topic))?;
+
+        let (header, body) = read_packet(&mut stream)?;
+        if header != CONNACK || body.len() != 2 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "expected CONNACK"));
+        }
+        if body[1] != 0 {
+            let reason = match body[1] {
+                1 => "unacceptable protocol version",
+                2 => "client identifier rejected",
+                3 => "server unavailable",
+                4 => "bad user name or password",
+                5 => "not authorized",
+                _ => "unknown reason",
+            };
+            return Err(io::Error::new(ErrorKind::ConnectionRefused, format!("broker refused connection: {}", reason)));
+        }
+
+        let mut connection = Self { stream, qos: settings.qos, status_topic: status_topic.to_string(), next_id: 0 };
+        connection.publish_status("online")?;
+        Ok(connection)
+    }
+
+    fn publish_status(&mut self, status: &str) -> io::Result<()> {
+        let message = Message { topic: self.status_topic.clone(), payload: status.as_bytes().to_vec(), retain: true };
+        self.publish(&message)
+    }
+
+    // Send `message` and, above QoS 0, wait for the broker to take it over
+    fn publish(&mut self, message: &Message) -> io::Result<()> {
+        self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+        let id = self.next_id;
+        self.stream.write_all(&publish_packet(message, self.qos, id))?;
+        match self.qos {
+            0 => Ok(()),
+            1 => self.await_packet(PUBACK, id),
+            _ => {
+                self.await_packet(PUBREC, id)?;
+                self.stream.write_all(&packet(PUBREL, &id.to_be_bytes()))?;
+                self.await_packet(PUBCOMP, id)
+            }
+        }
+    }
+
+    fn ping(&mut self) -> io::Result<()> {
+        self.stream.write_all(&packet(PINGREQ, &[]))?;
+        self.await_packet(PINGRESP, 0)
+    }
+
+    // Wait for a packet of type `kind` for packet `id`, skipping anything else
+    fn await_packet(&mut self, kind: u8, id: u16) -> io::Result<()> {
+        loop {
+            let (header, body) = read_packet(&mut self.stream)?;
+            let packet_id = if body.len() >= 2 { u16::from_be_bytes([body[0], body[1]]) } else { 0 };
+            if header & 0xF0 == kind & 0xF0 && packet_id == id {
+                return Ok(());
+            }
+        }
+    }
+
+    // Leave cleanly; the broker drops the will, so say "offline" ourselves
+    fn close(mut self) -> io::Result<()> {
+        self.publish_status("offline")?;
+        self.stream.write_all(&packet(DISCONNECT, &[]))
+    }
+}
+
+/// Sends messages to the broker from its own thread, reconnecting with backoff
+/// while it is unreachable, so the broker never holds up metering
+pub struct MqttPublisher {
+    settings: MqttSettings,
+    sender: Option<SyncSender<Message>>,
+    worker: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+    next_levels_s: f64,
+}
+
+impl MqttPublisher {
+    /// Start connecting in the background; only invalid settings fail here
+    pub fn start(settings: MqttSettings) -> io::Result<Self> {
+        if settings.qos > 2 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "MQTT QoS must be 0, 1 or 2"));
+        }
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LEN);
+        let stop = Arc::new(AtomicBool::new(false));
+        let connected = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let (settings, stop, connected) = (settings.clone(), Arc::clone(&stop), Arc::clone(&connected));
+            thread::spawn(move || run(settings, receiver, &stop, &connected))
+        };
+        Ok(Self { settings, sender: Some(sender), worker: Some(worker), stop, connected, next_levels_s: 0.0 })
+    }
+
+    /// Whether the broker connection is currently up
+    pub fn connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Whether a level summary is due at `elapsed_s` seconds of audio
+    pub fn levels_due(&self, elapsed_s: f64) -> bool {
+        // Same tolerance as the level log so rounding in the interval grid skips nothing
+        elapsed_s >= self.next_levels_s - 1e-9
+    }
+
+    /// Queue a level summary for the level topic
+    pub fn publish_levels(&mut self, summary: &LevelSummary) -> io::Result<()> {
+        let topic = render_topic(&self.settings.level_topic, &[("client_id", &self.settings.client_id)]);
+        let payload = serde_json::to_vec(summary).map_err(io::Error::other)?;
+        self.send(Message { topic, payload, retain: self.settings.retain_levels });
+
+        let interval = self.settings.level_interval_s;
+        self.next_levels_s = if interval > 0.0 {
+            ((summary.elapsed_s / interval).floor() + 1.0) * interval
+        } else {
+            summary.elapsed_s
+        };
+        Ok(())
+    }
+
+    /// Publish an alert event, already rendered as JSON, under the alert topic
+    pub fn publish_alert(&self, rule: &str, event: &str, payload: String) {
+        let topic = render_topic(
+            &self.settings.alert_topic,
+            &[("client_id", &self.settings.client_id), ("rule", rule), ("event", event)],
+        );
+        self.send(Message { topic, payload: payload.into_bytes(), retain: self.settings.retain_alerts });
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(sender) = &self.sender {
+            // A full queue means the broker has been gone for a while; the newest readings are lost
+            let _ = sender.try_send(message);
+        }
+    }
+
+    /// Deliver what is queued if connected, publish "offline" and disconnect
+    pub fn finish(mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+// Connection loop of the worker thread
+fn run(settings: MqttSettings, receiver: Receiver<Message>, stop: &AtomicBool, connected: &AtomicBool) {
+    let status_topic = render_topic(&settings.status_topic, &[("client_id", &settings.client_id)]);
+    let max_backoff = Duration::from_secs_f32(settings.reconnect_max_s.max(MIN_BACKOFF.as_secs_f32()));
+    let ping_interval = Duration::from_secs((settings.keep_alive_s as u64 / 2).max(1));
+    let mut backoff = MIN_BACKOFF;
+    let mut unsent: Option<Message> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        let mut connection = match Connection::open(&settings, &status_topic) {
+            Ok(connection) => connection,
+            Err(_) => {
+                // Wait out the backoff in short steps so finishing is not held up
+                let until = Instant::now() + backoff;
+                while Instant::now() < until && !stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                backoff = (backoff * 2).min(max_backoff);
+                continue;
+            }
+        };
+        connected.store(true, Ordering::Relaxed);
+        backoff = MIN_BACKOFF;
+
+        let result: io::Result<bool> = loop {
+            // A message the last connection failed on goes first
+            let message = match unsent.take().map_or_else(|| receiver.recv_timeout(ping_interval), Ok) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) if settings.keep_alive_s > 0 => match connection.ping() {
+                    Ok(()) => continue,
+                    Err(err) => break Err(err),
+                },
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break Ok(true),
+            };
+            if let Err(err) = connection.publish(&message) {
+                unsent = Some(message);
+                break Err(err);
+            }
+        };
+        connected.store(false, Ordering::Relaxed);
+        if let Ok(true) = result {
+            let _ = connection.close();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn string(body: &[u8], offset: &mut usize) -> String {
+        let length = u16::from_be_bytes([body[*offset], body[*offset + 1]]) as usize;
+        let value = String::from_utf8(body[*offset + 2..*offset + 2 + length].to_vec()).unwrap();
+        *offset += 2 + length;
+        value
+    }
+
+    // (topic, payload, retain) of a PUBLISH, acknowledging it at QoS 1
+    fn receive_publish(client: &mut TcpStream) -> (String, String, bool) {
+        let (header, body) = read_packet(client).unwrap();
+        assert_eq!(header & 0xF0, PUBLISH, "header {:#x}", header);
+        assert_eq!((header >> 1) & 0x03, 1);
+        let mut offset = 0;
+        let topic = string(&body, &mut offset);
+        client.write_all(&packet(PUBACK, &body[offset..offset + 2])).unwrap();
+        let payload = String::from_utf8(body[offset + 2..].to_vec()).unwrap();
+        (topic, payload, header & 0x01 != 0)
+    }
+
+    fn summary(elapsed_s: f64) -> LevelSummary {
+        LevelSummary {
+            timestamp: "2024-05-01T12:30:00.000Z".to_string(),
+            elapsed_s,
+            duration_s: 1.0,
+            unit: "dB(A)".to_string(),
+            db: -30.0,
+            leq: -31.5,
+            lmin: -40.0,
+            lmax: -25.0,
+            l10: -27.0,
+            l50: -31.0,
+            l90: -38.0,
+            l95: -39.0,
+            alert: false,
+            alerts: Vec::new(),
+            channels_db: vec![-30.0],
+        }
+    }
+
+    #[test]
+    fn encodes_packets() {
+        let mut length = Vec::new();
+        push_length(&mut length, 321);
+        assert_eq!(length, vec![0xC1, 0x02]);
+        assert_eq!(read_packet(&mut &[0x30, 0xC1, 0x02][..]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        let message = Message { topic: "a/b".to_string(), payload: b"hi".to_vec(), retain: true };
+        assert_eq!(publish_packet(&message, 1, 7), vec![0x33, 9, 0, 3, b'a', b'/', b'b', 0, 7, b'h', b'i']);
+
+        assert_eq!(
+            render_topic("site/{client_id}/alerts/{rule}", &[("client_id", "hall"), ("rule", "loud/#1")]),
+            "site/hall/alerts/loud__1"
+        );
+
+        // The password goes out only together with a user name
+        let settings = MqttSettings { client_id: "c".to_string(), password: Some("pw".to_string()), ..MqttSettings::default() };
+        let connect = connect_packet(&settings, "s");
+        assert_eq!(connect[9] & 0xC0, 0);
+        assert!(!connect.ends_with(b"pw"));
+        let settings = MqttSettings { username: Some("u".to_string()), ..settings };
+        let connect = connect_packet(&settings, "s");
+        assert_eq!(connect[9] & 0xC0, 0xC0);
+        assert!(connect.ends_with(&[0, 1, b'u', 0, 2, b'p', b'w']));
+    }
+
+    #[test]
+    fn publishes_to_broker_and_reconnects() {
+        let broker = TcpListener::bind("127.0.0.1:0").unwrap();
+        let settings = MqttSettings {
+            broker: broker.local_addr().unwrap().to_string(),
+            client_id: "hall".to_string(),
+            username: Some("meter".to_string()),
+            qos: 1,
+            level_interval_s: 1.0,
+            retain_alerts: true,
+            ..MqttSettings::default()
+        };
+        let mut publisher = MqttPublisher::start(settings).unwrap();
+
+        // First session: check the CONNECT, take the status, then drop the connection
+        let (mut client, _) = broker.accept().unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let (header, body) = read_packet(&mut client).unwrap();
+        assert_eq!(header, CONNECT);
+        let mut offset = 0;
+        assert_eq!(string(&body, &mut offset), "MQTT");
+        assert_eq!(&body[offset..offset + 2], &[4, 0x02 | 0x04 | 0x08 | 0x20 | 0x80]);
+        offset += 4;
+        let fields: Vec<String> = (0..4).map(|_| string(&body, &mut offset)).collect();
+        assert_eq!(fields, vec!["hall", "db_meter/hall/status", "offline", "meter"]);
+        client.write_all(&[CONNACK, 2, 0, 0]).unwrap();
+        assert_eq!(receive_publish(&mut client), ("db_meter/hall/status".to_string(), "online".to_string(), true));
+        drop(client);
+
+        // Published while the broker is away, delivered after reconnecting
+        assert!(publisher.levels_due(0.0));
+        publisher.publish_levels(&summary(0.5)).unwrap();
+        assert!(!publisher.levels_due(0.9) && publisher.levels_due(1.0));
+        publisher.publish_alert("loud", "triggered", "{\"event\":\"triggered\"}".to_string());
+
+        let (mut client, _) = broker.accept().unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(read_packet(&mut client).unwrap().0, CONNECT);
+        client.write_all(&[CONNACK, 2, 0, 0]).unwrap();
+        assert_eq!(receive_publish(&mut client).1, "online");
+        let (topic, payload, retain) = receive_publish(&mut client);
+        assert_eq!((topic.as_str(), retain), ("db_meter/hall/levels", false));
+        let levels: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(levels["leq"], -31.5);
+        let (topic, _, retain) = receive_publish(&mut client);
+        assert_eq!((topic.as_str(), retain), ("db_meter/hall/alerts/loud", true));
+        assert!(publisher.connected());
+
+        // Finishing says goodbye
+        let finisher = thread::spawn(move || publisher.finish());
+        assert_eq!(receive_publish(&mut client), ("db_meter/hall/status".to_string(), "offline".to_string(), true));
+        assert_eq!(read_packet(&mut client).unwrap(), (DISCONNECT, Vec::new()));
+        finisher.join().unwrap();
+    }
+}
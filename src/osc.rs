@@ -0,0 +1,203 @@
+use std::io;
+use std::net::UdpSocket;
+use serde::{Deserialize, Serialize};
+
+/// Where to send and under which addresses, as written in config.json. Level
+/// and peak addresses may contain {channel} ("1", "2", ... or "main"), the
+/// alert address {rule}
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OscSettings {
+    /// host:port of the receiving application
+    pub target: String,
+    /// Bundles per second of audio. Readings change once per block, so this is
+    /// at most 1000 / `block_ms` (10 with the default 100 ms blocks)
+    pub rate_hz: f32,
+    /// Address of level messages
+    pub level_address: String,
+    /// Address of peak messages
+    pub peak_address: String,
+    /// Address of alert messages
+    pub alert_address: String,
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        Self {
+            target: "127.0.0.1:9000".to_string(),
+            rate_hz: 10.0,
+            level_address: "/db_meter/{channel}/level".to_string(),
+            peak_address: "/db_meter/{channel}/peak".to_string(),
+            alert_address: "/db_meter/alert/{rule}".to_string(),
+        }
+    }
+}
+
+/// Readings of one channel, or of the main reading
+#[derive(Debug, Clone)]
+pub struct OscChannel {
+    /// Filled in for {channel}
+    pub name: String,
+    /// Normalized 0-100 level
+    pub level: f32,
+    /// RMS reading in the meter's unit
+    pub db: f32,
+    /// Peak as a normalized 0-100 level
+    pub peak_level: f32,
+    /// Peak in dBFS
+    pub peak_db: f32,
+}
+
+/// Argument of an OSC message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OscArg {
+    /// 32-bit float, tag 'f'
+    Float(f32),
+    /// 32-bit integer, tag 'i'
+    Int(i32),
+}
+
+// OSC strings are null-terminated and padded to a multiple of four bytes
+fn push_string(packet: &mut Vec<u8>, value: &str) {
+    packet.extend_from_slice(value.as_bytes());
+    packet.push(0);
+    while !packet.len().is_multiple_of(4) {
+        packet.push(0);
+    }
+}
+
+/// Message for `address` with its type tag string and big-endian arguments
+pub fn encode_message(address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    push_string(&mut packet, address);
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            OscArg::Float(_) => 'f',
+            OscArg::Int(_) => 'i',
+        }))
+        .collect();
+    push_string(&mut packet, &tags);
+    for arg in args {
+        match arg {
+            OscArg::Float(value) => packet.extend_from_slice(&value.to_be_bytes()),
+            OscArg::Int(value) => packet.extend_from_slice(&value.to_be_bytes()),
+        }
+    }
+    packet
+}
+
+/// Bundle to be handled immediately, so all readings of an update arrive together
+pub fn encode_bundle(messages: &[Vec<u8>]) -> Vec<u8> {
+    let mut bundle = Vec::new();
+    push_string(&mut bundle, "#bundle");
+    bundle.extend_from_slice(&1u64.to_be_bytes()); // The "immediately" time tag
+    for message in messages {
+        bundle.extend_from_slice(&(message.len() as i32).to_be_bytes());
+        bundle.extend_from_slice(message);
+    }
+    bundle
+}
+
+/// Fill `{name}` placeholders into an address template, keeping characters OSC
+/// reserves for patterns out of the values
+pub fn render_address(template: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(template.to_string(), |address, (name, value)| {
+        let value: String = value
+            .chars()
+            .map(|ch| if " #*,/?[]{}".contains(ch) { '_' } else { ch })
+            .collect();
+        address.replace(&format!("{{{}}}", name), &value)
+    })
+}
+
+/// Sends a bundle of level, peak and alert messages over UDP at the configured
+/// rate. Levels go out as 0-1 followed by the reading in dB; alerts as 1 or 0
+pub struct OscSender {
+    settings: OscSettings,
+    socket: UdpSocket,
+    next_send_s: f64,
+}
+
+impl OscSender {
+    /// Resolve the target up front so a bad address shows up at startup
+    pub fn new(settings: OscSettings) -> io::Result<Self> {
+        let socket = UdpSocket::bind(if settings.target.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" })?;
+        socket.connect(&settings.target)?;
+        Ok(Self { settings, socket, next_send_s: 0.0 })
+    }
+
+    /// Whether a bundle is due at `elapsed_s` seconds of audio
+    pub fn due(&self, elapsed_s: f64) -> bool {
+        elapsed_s >= self.next_send_s - 1e-9
+    }
+
+    /// Send the readings of `channels` and the state of each alert rule, and schedule the next bundle
+    pub fn send(&mut self, elapsed_s: f64, channels: &[OscChannel], alerts: &[(&str, bool)]) {
+        let mut messages = Vec::new();
+        for channel in channels {
+            let values = [("channel", channel.name.as_str())];
+            let level = (channel.level / 100.0).clamp(0.0, 1.0);
+            let peak = (channel.peak_level / 100.0).clamp(0.0, 1.0);
+            messages.push(encode_message(
+                &render_address(&self.settings.level_address, &values),
+                &[OscArg::Float(level), OscArg::Float(channel.db)],
+            ));
+            messages.push(encode_message(
+                &render_address(&self.settings.peak_address, &values),
+                &[OscArg::Float(peak), OscArg::Float(channel.peak_db)],
+            ));
+        }
+        for (rule, active) in alerts {
+            let address = render_address(&self.settings.alert_address, &[("rule", rule)]);
+            messages.push(encode_message(&address, &[OscArg::Int(*active as i32)]));
+        }
+        // Visuals may start after the meter, so nobody listening is not an error
+        let _ = self.socket.send(&encode_bundle(&messages));
+
+        let interval = 1.0 / self.settings.rate_hz.max(0.1) as f64;
+        self.next_send_s = ((elapsed_s / interval).floor() + 1.0) * interval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn encodes_messages_and_bundles() {
+        let message = encode_message("/lvl", &[OscArg::Float(0.5), OscArg::Int(-1)]);
+        let mut expected = b"/lvl\0\0\0\0,fi\0".to_vec();
+        expected.extend_from_slice(&[0x3F, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(message, expected);
+
+        let bundle = encode_bundle(std::slice::from_ref(&message));
+        assert_eq!(&bundle[..16], b"#bundle\0\0\0\0\0\0\0\0\x01");
+        assert_eq!(&bundle[16..20], &(message.len() as i32).to_be_bytes());
+        assert_eq!(&bundle[20..], &message[..]);
+
+        assert_eq!(render_address("/m/alert/{rule}", &[("rule", "loud hall/1")]), "/m/alert/loud_hall_1");
+    }
+
+    #[test]
+    fn sends_bundles_over_udp() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let settings = OscSettings { target: receiver.local_addr().unwrap().to_string(), rate_hz: 10.0, ..OscSettings::default() };
+        let mut sender = OscSender::new(settings).unwrap();
+
+        let channel = OscChannel { name: "main".to_string(), level: 50.0, db: -50.0, peak_level: 150.0, peak_db: 3.0 };
+        assert!(sender.due(0.0));
+        sender.send(0.05, &[channel], &[("loud", true)]);
+        assert!(!sender.due(0.09) && sender.due(0.1));
+
+        let mut datagram = [0u8; 1024];
+        let length = receiver.recv(&mut datagram).unwrap();
+        let expected = encode_bundle(&[
+            encode_message("/db_meter/main/level", &[OscArg::Float(0.5), OscArg::Float(-50.0)]),
+            encode_message("/db_meter/main/peak", &[OscArg::Float(1.0), OscArg::Float(3.0)]),
+            encode_message("/db_meter/alert/loud", &[OscArg::Int(1)]),
+        ]);
+        assert_eq!(&datagram[..length], &expected[..]);
+    }
+}
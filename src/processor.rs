@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+use crate::loudness::{Loudness, LoudnessMeter};
+use crate::peak::TruePeakFilter;
+use crate::scale::MeterScale;
+use crate::source::SourceFormat;
+
+/// Per-block measurements the meter is built from
+pub trait SoundProcessor {
+    /// Root mean square of `samples`
+    fn calculate_rms(&self, samples: &[f32]) -> f32;
+    /// Highest absolute sample value
+    fn calculate_peak(&self, samples: &[f32]) -> f32;
+    /// Highest absolute value between samples, with `filter` carrying the channel's history
+    fn calculate_true_peak(&self, filter: &mut TruePeakFilter, samples: &[f32]) -> f32;
+    /// Linear value in dB, with silence floored at -200 dB
+    fn calculate_db(&self, rms: f32) -> f32;
+    /// Deflection of the bar for a dB reading, 0-100
+    fn normalize_db_to_0_100(&self, db: f32) -> f32;
+
+    /// Prepare any state the processor keeps across blocks for a new source
+    fn configure(&mut self, _format: SourceFormat) {}
+
+    /// Feed a block of de-interleaved channels to processors that measure the
+    /// program as a whole, returning their latest reading
+    fn process_program(&mut self, _channels: &[Vec<f32>]) -> Option<Loudness> {
+        None
+    }
+}
+
+/// RMS and peak measurements, normalized onto a display scale
+#[derive(Default)]
+pub struct AudioProcessor {
+    scale: MeterScale,
+}
+
+impl AudioProcessor {
+    /// Processor normalizing onto `scale`
+    pub fn new(scale: MeterScale) -> Self {
+        Self { scale }
+    }
+}
+
+impl SoundProcessor for AudioProcessor {
+    fn calculate_rms(&self, samples: &[f32]) -> f32 {
+        let sum_of_squares: f32 = samples.iter().map(|&sample| sample * sample).sum();
+        (sum_of_squares / samples.len() as f32).sqrt()
+    }
+
+    fn calculate_peak(&self, samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, &sample| peak.max(sample.abs()))
+    }
+
+    fn calculate_true_peak(&self, filter: &mut TruePeakFilter, samples: &[f32]) -> f32 {
+        filter.process(samples)
+    }
+
+    fn calculate_db(&self, rms: f32) -> f32 {
+        20.0 * rms.max(1e-10).log10()
+    }
+
+    fn normalize_db_to_0_100(&self, db: f32) -> f32 {
+        self.scale.fraction(db) * 100.0
+    }
+}
+
+/// Processor adding BS.1770 / EBU R128 program loudness on top of the
+/// per-channel RMS and peak measurements of [`AudioProcessor`]
+pub struct LoudnessProcessor {
+    base: AudioProcessor,
+    meter: Option<LoudnessMeter>,
+}
+
+impl LoudnessProcessor {
+    /// Processor normalizing onto `scale`; the loudness meter starts with [`SoundProcessor::configure`]
+    pub fn new(scale: MeterScale) -> Self {
+        Self { base: AudioProcessor::new(scale), meter: None }
+    }
+}
+
+impl SoundProcessor for LoudnessProcessor {
+    fn calculate_rms(&self, samples: &[f32]) -> f32 {
+        self.base.calculate_rms(samples)
+    }
+
+    fn calculate_peak(&self, samples: &[f32]) -> f32 {
+        self.base.calculate_peak(samples)
+    }
+
+    fn calculate_true_peak(&self, filter: &mut TruePeakFilter, samples: &[f32]) -> f32 {
+        self.base.calculate_true_peak(filter, samples)
+    }
+
+    fn calculate_db(&self, rms: f32) -> f32 {
+        self.base.calculate_db(rms)
+    }
+
+    fn normalize_db_to_0_100(&self, db: f32) -> f32 {
+        self.base.normalize_db_to_0_100(db)
+    }
+
+    fn configure(&mut self, format: SourceFormat) {
+        self.meter = Some(LoudnessMeter::new(format.sample_rate, format.channels.max(1) as usize));
+    }
+
+    fn process_program(&mut self, channels: &[Vec<f32>]) -> Option<Loudness> {
+        let meter = self.meter.as_mut()?;
+        meter.process(channels);
+        Some(meter.loudness())
+    }
+}
+
+/// Quantity driving the main bar, min/max and alerts
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeterQuantity {
+    /// Unweighted RMS of each block
+    Rms,
+    /// EBU R128 momentary loudness (400 ms)
+    Momentary,
+    /// EBU R128 short-term loudness (3 s)
+    ShortTerm,
+    /// Gated integrated loudness since the start
+    Integrated,
+}
+
+impl MeterQuantity {
+    /// Processor measuring this quantity on `scale`
+    pub fn processor(self, scale: MeterScale) -> Box<dyn SoundProcessor + Send> {
+        match self {
+            MeterQuantity::Rms => Box::new(AudioProcessor::new(scale)),
+            _ => Box::new(LoudnessProcessor::new(scale)),
+        }
+    }
+
+    /// Unit of the main reading before weighting and calibration suffixes
+    pub fn unit(self) -> &'static str {
+        match self {
+            MeterQuantity::Rms => "dB",
+            _ => "LUFS",
+        }
+    }
+
+    /// The quantity's reading out of the program loudness; None for RMS
+    pub fn select(self, loudness: &Loudness) -> Option<f64> {
+        match self {
+            MeterQuantity::Rms => None,
+            MeterQuantity::Momentary => Some(loudness.momentary),
+            MeterQuantity::ShortTerm => Some(loudness.short_term),
+            MeterQuantity::Integrated => Some(loudness.integrated),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scale::DisplayScale;
+
+    fn sine(frequency: f32, sample_rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|n| (2.0 * std::f32::consts::PI * frequency * n as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn measures_rms_peak_and_db() {
+        let processor = AudioProcessor::new(MeterScale::new(DisplayScale::Linear, -100.0, 0.0));
+        let samples = sine(1000.0, 48_000, 4800);
+        let rms = processor.calculate_rms(&samples);
+        assert!((processor.calculate_db(rms) + 3.01).abs() < 0.01);
+        assert!((processor.calculate_peak(&[0.25, -0.5, 0.1]) - 0.5).abs() < 1e-6);
+        assert_eq!(processor.calculate_db(0.0), -200.0);
+        assert!((processor.normalize_db_to_0_100(-25.0) - 75.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn loudness_quantities_use_the_program_meter() {
+        let scale = MeterScale::default();
+        let mut processor = MeterQuantity::Momentary.processor(scale);
+        assert!(processor.process_program(&[vec![0.0; 480]]).is_none());
+
+        processor.configure(SourceFormat { sample_rate: 48_000, channels: 1 });
+        let mut loudness = None;
+        for _ in 0..10 {
+            loudness = processor.process_program(&[sine(1000.0, 48_000, 4800)]);
+        }
+        // A full-scale 1 kHz sine on one channel reads about -3 LUFS
+        let momentary = MeterQuantity::Momentary.select(&loudness.unwrap()).unwrap();
+        assert!((momentary + 3.0).abs() < 0.1, "{}", momentary);
+        assert!(MeterQuantity::Rms.select(&loudness.unwrap()).is_none());
+        assert_eq!(MeterQuantity::Integrated.unit(), "LUFS");
+    }
+}
@@ -0,0 +1,232 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+use crate::wav::{WavFormat, WavSpec, WavWriter};
+
+// How often the writer brings the WAV header up to date
+const HEADER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sample encoding of recordings
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordFormat {
+    /// 16-bit integer PCM
+    #[default]
+    Pcm16,
+    /// 24-bit integer PCM
+    Pcm24,
+    /// 32-bit IEEE float
+    Float32,
+}
+
+impl RecordFormat {
+    fn spec(self, sample_rate: u32, channels: u16) -> WavSpec {
+        let (bits_per_sample, format) = match self {
+            RecordFormat::Pcm16 => (16, WavFormat::Pcm),
+            RecordFormat::Pcm24 => (24, WavFormat::Pcm),
+            RecordFormat::Float32 => (32, WavFormat::Float),
+        };
+        WavSpec { channels, sample_rate, bits_per_sample, format }
+    }
+}
+
+/// Where and how to record
+#[derive(Debug, Clone)]
+pub struct RecordSettings {
+    /// Recording file, or the name segments are numbered after
+    pub path: PathBuf,
+    /// Sample encoding
+    pub format: RecordFormat,
+    /// Start a new numbered file after this much audio
+    pub segment_s: Option<f64>,
+}
+
+// Progress shared by the writer thread
+struct RecordStatus {
+    segment: AtomicUsize, // Current segment, counting from 1
+    bytes: AtomicU64,     // Size of the current file
+}
+
+/// Writes everything metered to WAV on its own thread, so slow disks hold up
+/// neither the audio callback nor the display
+pub struct Recorder {
+    settings: RecordSettings,
+    sender: Option<mpsc::Sender<Vec<f32>>>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+    status: Arc<RecordStatus>,
+    failed: bool,
+}
+
+/// File of `segment` (counting from 1): the configured path itself, or with
+/// segments "session.wav" becomes "session-001.wav", "session-002.wav", ...
+/// Unsegmented recordings only go on to "session-002.wav" when the first file
+/// reaches the WAV size limit
+pub fn segment_path(settings: &RecordSettings, segment: usize) -> PathBuf {
+    if settings.segment_s.is_none() && segment == 1 {
+        return settings.path.clone();
+    }
+    let stem = settings.path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = settings.path.extension().map_or("wav".into(), |extension| extension.to_string_lossy());
+    settings.path.with_file_name(format!("{}-{:03}.{}", stem, segment, extension))
+}
+
+impl Recorder {
+    /// Create the first file right away so that errors surface before metering starts
+    pub fn start(settings: RecordSettings, sample_rate: u32, channels: usize) -> io::Result<Self> {
+        let spec = settings.format.spec(sample_rate, channels as u16);
+        let first = WavWriter::create(&segment_path(&settings, 1), spec)?;
+        let status = Arc::new(RecordStatus { segment: AtomicUsize::new(1), bytes: AtomicU64::new(first.bytes_written()) });
+        let (sender, receiver) = mpsc::channel::<Vec<f32>>();
+
+        let writer = {
+            let settings = settings.clone();
+            let status = Arc::clone(&status);
+            // Files roll over before they outgrow the WAV header, segmented or not
+            let segment_frames = settings
+                .segment_s
+                .map_or(u64::MAX, |seconds| ((seconds * sample_rate as f64) as u64).max(1))
+                .min(WavWriter::max_frames(spec));
+            thread::spawn(move || {
+                let mut writer = first;
+                let mut frames = 0u64;
+                let mut last_flush = Instant::now();
+                for block in receiver {
+                    let mut rest = &block[..];
+                    while !rest.is_empty() {
+                        if frames == segment_frames {
+                            writer.finalize()?;
+                            let segment = status.segment.load(Ordering::Relaxed) + 1;
+                            writer = WavWriter::create(&segment_path(&settings, segment), spec)?;
+                            status.segment.store(segment, Ordering::Relaxed);
+                            frames = 0;
+                        }
+                        let take = rest.len().min(((segment_frames - frames) as usize).saturating_mul(channels));
+                        writer.write_samples(&rest[..take])?;
+                        frames += (take / channels) as u64;
+                        rest = &rest[take..];
+                        status.bytes.store(writer.bytes_written(), Ordering::Relaxed);
+                    }
+                    if last_flush.elapsed() >= HEADER_INTERVAL {
+                        writer.flush()?;
+                        last_flush = Instant::now();
+                    }
+                }
+                writer.finalize()
+            })
+        };
+
+        Ok(Self { settings, sender: Some(sender), writer: Some(writer), status, failed: false })
+    }
+
+    /// Queue interleaved samples for the writer thread
+    pub fn write(&mut self, samples: &[f32]) {
+        let sent = self.sender.as_ref().is_some_and(|sender| sender.send(samples.to_vec()).is_ok());
+        // The writer only hangs up after an error, which `finish` reports
+        self.failed |= !sent;
+    }
+
+    /// File currently being written
+    pub fn current_path(&self) -> PathBuf {
+        segment_path(&self.settings, self.status.segment.load(Ordering::Relaxed))
+    }
+
+    /// Short status for the meter line, e.g. "REC session-002.wav 12.4 MB"
+    pub fn status(&self) -> String {
+        if self.failed {
+            return "REC failed".to_string();
+        }
+        let path = self.current_path();
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        format!("REC {} {}", name, format_size(self.status.bytes.load(Ordering::Relaxed)))
+    }
+
+    /// Write out what is still queued and close the file
+    pub fn finish(mut self) -> io::Result<()> {
+        self.sender = None;
+        match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("recording thread panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Size in bytes as kB, MB or GB
+pub fn format_size(bytes: u64) -> String {
+    let bytes = bytes as f64;
+    if bytes >= 1e9 {
+        format!("{:.2} GB", bytes / 1e9)
+    } else if bytes >= 1e6 {
+        format!("{:.1} MB", bytes / 1e6)
+    } else {
+        format!("{:.0} kB", bytes / 1e3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav::WavReader;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("db_meter_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn records_everything_to_one_file() {
+        let dir = temp_dir("record");
+        let settings = RecordSettings { path: dir.join("session.wav"), format: RecordFormat::Float32, segment_s: None };
+        // Numbered only past the WAV size limit
+        assert_eq!(segment_path(&settings, 2), dir.join("session-002.wav"));
+        let mut recorder = Recorder::start(settings, 1000, 2).unwrap();
+        for block in 0..10 {
+            let samples: Vec<f32> = (0..200).map(|n| (block * 200 + n) as f32 / 4000.0).collect();
+            recorder.write(&samples);
+        }
+        assert_eq!(recorder.current_path(), dir.join("session.wav"));
+        recorder.finish().unwrap();
+
+        let mut reader = WavReader::open(&dir.join("session.wav")).unwrap();
+        assert_eq!(reader.spec().sample_rate, 1000);
+        assert_eq!(reader.total_frames(), 1000);
+        let mut samples = Vec::new();
+        reader.read_frames(1000, &mut samples).unwrap();
+        assert_eq!(samples[1999], 1999.0 / 4000.0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn splits_into_segments() {
+        let dir = temp_dir("segments");
+        let settings = RecordSettings { path: dir.join("session.wav"), format: RecordFormat::Pcm16, segment_s: Some(0.25) };
+        let mut recorder = Recorder::start(settings, 1000, 1).unwrap();
+        // 0.6 s in blocks that do not line up with the segment length
+        for _ in 0..6 {
+            recorder.write(&[0.5; 100]);
+        }
+        recorder.finish().unwrap();
+
+        let frames: Vec<u64> = (1..=3)
+            .map(|segment| WavReader::open(&dir.join(format!("session-{:03}.wav", segment))).unwrap().total_frames())
+            .collect();
+        assert_eq!(frames, vec![250, 250, 100]);
+        assert!(!dir.join("session-004.wav").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(44), "0 kB");
+        assert_eq!(format_size(12_400_000), "12.4 MB");
+        assert_eq!(format_size(2_500_000_000), "2.50 GB");
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Fixed-capacity single-producer single-consumer queue of samples. Slots are
+// atomics holding the f32 bits, so neither side ever locks or allocates
+struct RingBuffer {
+    slots: Box<[AtomicU32]>,
+    write: AtomicUsize, // Total samples written, only advanced by the producer
+    read: AtomicUsize,  // Total samples read, only advanced by the consumer
+}
+
+// Writing half, owned by the audio callback
+pub struct Producer {
+    ring: Arc<RingBuffer>,
+}
+
+// Reading half, owned by the metering thread
+pub struct Consumer {
+    ring: Arc<RingBuffer>,
+}
+
+pub fn ring_buffer(capacity: usize) -> (Producer, Consumer) {
+    let ring = Arc::new(RingBuffer {
+        slots: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+        write: AtomicUsize::new(0),
+        read: AtomicUsize::new(0),
+    });
+    (Producer { ring: Arc::clone(&ring) }, Consumer { ring })
+}
+
+impl Producer {
+    // Samples that can be pushed without overwriting unread ones
+    pub fn free(&self) -> usize {
+        let ring = &self.ring;
+        let used = ring.write.load(Ordering::Relaxed).wrapping_sub(ring.read.load(Ordering::Acquire));
+        ring.slots.len() - used
+    }
+
+    // Push as many of `samples` as fit and return how many were written
+    pub fn push(&mut self, samples: &[f32]) -> usize {
+        let ring = &self.ring;
+        let count = samples.len().min(self.free());
+        let write = ring.write.load(Ordering::Relaxed);
+        for (offset, &sample) in samples[..count].iter().enumerate() {
+            ring.slots[write.wrapping_add(offset) % ring.slots.len()].store(sample.to_bits(), Ordering::Relaxed);
+        }
+        // Publish the samples only once they are all in place
+        ring.write.store(write.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+impl Consumer {
+    // Samples waiting to be read
+    pub fn available(&self) -> usize {
+        let ring = &self.ring;
+        ring.write.load(Ordering::Acquire).wrapping_sub(ring.read.load(Ordering::Relaxed))
+    }
+
+    // Move up to `out.len()` samples into `out` and return how many were read
+    pub fn pop(&mut self, out: &mut [f32]) -> usize {
+        let ring = &self.ring;
+        let count = out.len().min(self.available());
+        let read = ring.read.load(Ordering::Relaxed);
+        for (offset, sample) in out[..count].iter_mut().enumerate() {
+            *sample = f32::from_bits(ring.slots[read.wrapping_add(offset) % ring.slots.len()].load(Ordering::Relaxed));
+        }
+        // Hand the slots back to the producer only after they have been copied
+        ring.read.store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn wraps_around_and_refuses_overflow() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        assert_eq!(producer.push(&[1.0, 2.0, 3.0]), 3);
+        let mut out = [0.0; 2];
+        assert_eq!(consumer.pop(&mut out), 2);
+        assert_eq!(out, [1.0, 2.0]);
+
+        assert_eq!(producer.push(&[4.0, 5.0, 6.0, 7.0]), 3);
+        assert_eq!(producer.free(), 0);
+        let mut out = [0.0; 8];
+        assert_eq!(consumer.pop(&mut out), 4);
+        assert_eq!(out[..4], [3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(consumer.available(), 0);
+    }
+
+    #[test]
+    fn delivers_everything_in_order_across_threads() {
+        let (mut producer, mut consumer) = ring_buffer(64);
+        let total = 20_000;
+        let writer = thread::spawn(move || {
+            let mut next = 0;
+            while next < total {
+                let block: Vec<f32> = (next..(next + 17).min(total)).map(|n| n as f32).collect();
+                let mut written = 0;
+                while written < block.len() {
+                    match producer.push(&block[written..]) {
+                        0 => thread::yield_now(),
+                        count => written += count,
+                    }
+                }
+                next += block.len();
+            }
+        });
+
+        let mut expected = 0;
+        let mut out = [0.0; 23];
+        while expected < total {
+            let count = consumer.pop(&mut out);
+            if count == 0 {
+                thread::yield_now();
+            }
+            for &sample in &out[..count] {
+                assert_eq!(sample, expected as f32);
+                expected += 1;
+            }
+        }
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+// Level of the alignment tone (0 VU, PPM 4, DIN -9 dB) per EBU R68
+const ALIGNMENT_DBFS: f32 = -18.0;
+// 0 dB on a DIN 45406 meter sits 9 dB above alignment
+const DIN_ZERO_DBFS: f32 = ALIGNMENT_DBFS + 9.0;
+
+/// How a dBFS reading is mapped onto the width of the bar
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayScale {
+    /// Linear in dB between `scale_min_db` and `scale_max_db`
+    #[default]
+    Linear,
+    /// IEC 60268-18 log-compressed digital meter scale, -70..0 dBFS
+    Iec60268,
+    /// DIN 45406 (IEC 60268-10 Type I), -50..+5 dB with 0 dB = -9 dBFS
+    PpmType1,
+    /// BBC / EBU (IEC 60268-10 Type II), marks 1..7 at 4 dB steps, 4 = -18 dBFS
+    PpmType2,
+    /// Volume indicator, -20..+3 VU with 0 VU = -18 dBFS, deflection linear in voltage
+    Vu,
+}
+
+/// A display scale together with the range used by the linear scale
+#[derive(Debug, Clone, Copy)]
+pub struct MeterScale {
+    scale: DisplayScale,
+    min_db: f32,
+    max_db: f32,
+}
+
+impl Default for MeterScale {
+    fn default() -> Self {
+        Self::new(DisplayScale::Linear, -100.0, 0.0)
+    }
+}
+
+impl MeterScale {
+    /// Scale of type `scale`; `min_db` and `max_db` only matter to the linear one
+    pub fn new(scale: DisplayScale, min_db: f32, max_db: f32) -> Self {
+        Self { scale, min_db, max_db }
+    }
+
+    /// Deflection of the bar for a dBFS reading, 0.0 to 1.0
+    pub fn fraction(&self, db: f32) -> f32 {
+        let fraction = match self.scale {
+            DisplayScale::Linear => (db - self.min_db) / (self.max_db - self.min_db),
+            DisplayScale::Iec60268 => iec_60268_18(db),
+            DisplayScale::PpmType1 => (db - DIN_ZERO_DBFS + 50.0) / 55.0,
+            DisplayScale::PpmType2 => (db - ppm_mark_dbfs(0.0)) / (ppm_mark_dbfs(8.0) - ppm_mark_dbfs(0.0)),
+            DisplayScale::Vu => 10f32.powf((db - ALIGNMENT_DBFS - 3.0) / 20.0),
+        };
+        if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.clamp(0.0, 1.0)
+        }
+    }
+
+    /// Labelled tick marks as (dBFS, label) pairs, in ascending order
+    pub fn ticks(&self) -> Vec<(f32, String)> {
+        match self.scale {
+            DisplayScale::Linear => {
+                let span = self.max_db - self.min_db;
+                let step = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0]
+                    .into_iter()
+                    .find(|&step| span / step <= 10.0)
+                    .unwrap_or(100.0);
+                let first = (self.min_db / step).ceil() as i32;
+                let last = (self.max_db / step).floor() as i32;
+                (first..=last)
+                    .map(|n| {
+                        let db = n as f32 * step;
+                        (db, format!("{}", db))
+                    })
+                    .collect()
+            }
+            DisplayScale::Iec60268 => [-60, -50, -40, -30, -20, -10, -5, 0]
+                .into_iter()
+                .map(|db| (db as f32, db.to_string()))
+                .collect(),
+            DisplayScale::PpmType1 => [-50, -40, -30, -20, -10, -5, 0, 5]
+                .into_iter()
+                .map(|din| (DIN_ZERO_DBFS + din as f32, format!("{:+}", din).replace("+0", "0")))
+                .collect(),
+            DisplayScale::PpmType2 => (1..=7)
+                .map(|mark| (ppm_mark_dbfs(mark as f32), mark.to_string()))
+                .collect(),
+            DisplayScale::Vu => [-20, -10, -7, -5, -3, -2, -1, 0, 1, 2, 3]
+                .into_iter()
+                .map(|vu| (ALIGNMENT_DBFS + vu as f32, format!("{:+}", vu).replace("+0", "0")))
+                .collect(),
+        }
+    }
+
+    /// Tick line to print above a bar of `width` characters drawn as "[...]":
+    /// a '|' at every tick followed by its label where there is room for it.
+    /// Empty for a bar without cells
+    pub fn tick_line(&self, width: usize) -> String {
+        if width == 0 {
+            return String::new();
+        }
+        let mut line = vec![' '; width + 2];
+        let mut free_from = 0;
+        for (db, label) in self.ticks() {
+            let column = ((self.fraction(db) * width as f32).round() as usize).clamp(1, width);
+            if column < free_from {
+                continue;
+            }
+            line[column] = '|';
+            let end = column + 1 + label.chars().count();
+            if end <= line.len() {
+                for (cell, ch) in line[column + 1..end].iter_mut().zip(label.chars()) {
+                    *cell = ch;
+                }
+                free_from = end + 1;
+            } else {
+                free_from = column + 2;
+            }
+        }
+        line.into_iter().collect::<String>().trim_end().to_string()
+    }
+}
+
+// dBFS level of a mark on the BBC PPM, mark 4 being the alignment level
+fn ppm_mark_dbfs(mark: f32) -> f32 {
+    ALIGNMENT_DBFS + 4.0 * (mark - 4.0)
+}
+
+// Piecewise-linear deflection of the IEC 60268-18 scale: the steps get wider
+// towards full scale so the working range takes up most of the meter
+fn iec_60268_18(db: f32) -> f32 {
+    let percent = if db < -70.0 {
+        0.0
+    } else if db < -60.0 {
+        (db + 70.0) * 0.25
+    } else if db < -50.0 {
+        (db + 60.0) * 0.5 + 2.5
+    } else if db < -40.0 {
+        (db + 50.0) * 0.75 + 7.5
+    } else if db < -30.0 {
+        (db + 40.0) * 1.5 + 15.0
+    } else if db < -20.0 {
+        (db + 30.0) * 2.0 + 30.0
+    } else {
+        (db + 20.0) * 2.5 + 50.0
+    };
+    percent / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn linear_scale_uses_configured_range() {
+        let scale = MeterScale::new(DisplayScale::Linear, -80.0, -20.0);
+        assert_close(scale.fraction(-80.0), 0.0);
+        assert_close(scale.fraction(-50.0), 0.5);
+        assert_close(scale.fraction(-20.0), 1.0);
+        assert_close(scale.fraction(0.0), 1.0);
+        assert_close(scale.fraction(f32::NEG_INFINITY), 0.0);
+    }
+
+    #[test]
+    fn iec_scale_breakpoints() {
+        let scale = MeterScale::new(DisplayScale::Iec60268, 0.0, 0.0);
+        for (db, percent) in [(-70.0, 0.0), (-60.0, 2.5), (-50.0, 7.5), (-40.0, 15.0), (-30.0, 30.0), (-20.0, 50.0), (0.0, 100.0)] {
+            assert_close(scale.fraction(db), percent / 100.0);
+        }
+    }
+
+    #[test]
+    fn broadcast_scales_are_aligned_at_minus_18() {
+        let vu = MeterScale::new(DisplayScale::Vu, 0.0, 0.0);
+        assert_close(vu.fraction(-18.0), 10f32.powf(-3.0 / 20.0));
+        assert_close(vu.fraction(-15.0), 1.0);
+
+        let ppm = MeterScale::new(DisplayScale::PpmType2, 0.0, 0.0);
+        assert_close(ppm.fraction(-18.0), 0.5);
+        assert_eq!(ppm.ticks()[3], (-18.0, "4".to_string()));
+
+        let din = MeterScale::new(DisplayScale::PpmType1, 0.0, 0.0);
+        assert_close(din.fraction(-9.0), 50.0 / 55.0);
+        assert!(din.ticks().contains(&(-9.0, "0".to_string())));
+    }
+
+    #[test]
+    fn ticks_ascend_and_fit_the_bar() {
+        for scale in [DisplayScale::Linear, DisplayScale::Iec60268, DisplayScale::PpmType1, DisplayScale::PpmType2, DisplayScale::Vu] {
+            let scale = MeterScale::new(scale, -100.0, 0.0);
+            let ticks = scale.ticks();
+            assert!(ticks.windows(2).all(|pair| pair[0].0 < pair[1].0), "{:?}", ticks);
+            assert!(scale.tick_line(50).chars().count() <= 52);
+        }
+    }
+
+    #[test]
+    fn tick_line_places_marks_at_deflection() {
+        let scale = MeterScale::new(DisplayScale::Linear, -100.0, 0.0);
+        assert_eq!(scale.tick_line(0), "");
+        let line = scale.tick_line(100);
+        assert!(line.starts_with(" |-100"), "{}", line);
+        assert_eq!(line.chars().nth(50), Some('|'));
+        assert_eq!(line.chars().nth(100), Some('|'));
+    }
+}
@@ -0,0 +1,674 @@
+use std::f32::consts::PI;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use serde::{Deserialize, Serialize};
+
+use crate::devices;
+use crate::wav::WavReader;
+
+/// Callback receiving interleaved blocks of samples in -1.0..1.0; returning
+/// false asks the source to stop
+pub type BlockSink = Box<dyn FnMut(&[f32]) -> bool + Send>;
+
+/// Layout of the interleaved samples a source delivers
+#[derive(Debug, Clone, Copy)]
+pub struct SourceFormat {
+    /// Frames per second
+    pub sample_rate: u32,
+    /// Samples per frame
+    pub channels: u16,
+}
+
+/// Trait for anything that can feed samples into the metering pipeline
+pub trait SampleSource {
+    /// Description shown when metering starts
+    fn name(&self) -> String;
+    /// Layout of the samples `run` delivers
+    fn format(&self) -> SourceFormat;
+    /// Push blocks into `sink` until the source ends or the sink declines more
+    /// samples; errors end the source early
+    fn run(self: Box<Self>, sink: BlockSink) -> Result<(), String>;
+
+    /// Whether the samples arrive on stdin, leaving no keyboard input for the front end
+    fn reads_stdin(&self) -> bool {
+        false
+    }
+
+    /// Whether `sink` is called from a real-time callback that must never block
+    fn is_live(&self) -> bool {
+        false
+    }
+
+    /// Counter of errors the source reported while running, for sources that can fail midway
+    fn errors(&self) -> Option<Arc<AtomicU64>> {
+        None
+    }
+}
+
+/// Encoding of raw PCM read from stdin
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawEncoding {
+    /// Signed 16-bit little-endian integers
+    S16le,
+    /// 32-bit little-endian floats
+    F32le,
+}
+
+/// Signal of the test generator
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Waveform {
+    /// Sine wave at the configured frequency
+    Sine,
+    /// Uniform white noise
+    Noise,
+    /// Digital silence
+    Silence,
+}
+
+/// Source selection as stored in config.json
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceConfig {
+    /// Live input from an audio device
+    #[default]
+    Device,
+    /// A WAV file
+    File {
+        /// File to read
+        path: PathBuf,
+        /// Deliver the file at its real-time rate instead of as fast as possible
+        #[serde(default)]
+        realtime: bool,
+    },
+    /// Raw interleaved PCM on stdin
+    Stdin {
+        /// Frames per second
+        sample_rate: u32,
+        /// Samples per frame
+        channels: u16,
+        /// Sample encoding
+        encoding: RawEncoding,
+    },
+    /// Synthetic test signal
+    Generator {
+        /// Signal shape
+        waveform: Waveform,
+        /// Frequency of the sine in Hz
+        frequency: f32,
+        /// Peak amplitude, 1.0 for full scale
+        amplitude: f32,
+        /// Frames per second
+        sample_rate: u32,
+        /// Samples per frame, all carrying the same signal
+        channels: u16,
+        /// Length of the signal, endless when unset
+        duration_s: Option<f32>,
+    },
+}
+
+impl SourceConfig {
+    /// Build the configured source; non-device sources deliver blocks of `block_ms`
+    pub fn open(
+        &self,
+        block_ms: u32,
+        host: Option<&str>,
+        device: Option<&str>,
+    ) -> Result<Box<dyn SampleSource>, String> {
+        let block_frames = |sample_rate: u32| (sample_rate as u64 * block_ms as u64 / 1000).max(1) as usize;
+        Ok(match self {
+            SourceConfig::Device => Box::new(CpalSource::open(host, device)?),
+            SourceConfig::File { path, realtime } => {
+                let source = FileSource::open(path, block_ms)
+                    .map_err(|err| format!("Unable to open {}: {}", path.display(), err))?;
+                Box::new(FileSource { realtime: *realtime, ..source })
+            }
+            SourceConfig::Stdin { sample_rate, channels, encoding } => Box::new(StdinSource {
+                format: raw_format(*sample_rate, *channels)?,
+                encoding: *encoding,
+                block_frames: block_frames(*sample_rate),
+            }),
+            SourceConfig::Generator { waveform, frequency, amplitude, sample_rate, channels, duration_s } => {
+                Box::new(Generator {
+                    format: raw_format(*sample_rate, *channels)?,
+                    waveform: *waveform,
+                    frequency: *frequency,
+                    amplitude: *amplitude,
+                    duration: duration_s.map(Duration::from_secs_f32),
+                    block_frames: block_frames(*sample_rate),
+                    phase: 0.0,
+                    noise_state: 0x2545_f491,
+                })
+            }
+        })
+    }
+}
+
+// Format of a source whose layout comes from the config rather than a file or device
+fn raw_format(sample_rate: u32, channels: u16) -> Result<SourceFormat, String> {
+    if sample_rate == 0 {
+        return Err("The source's sample_rate must be above 0".to_string());
+    }
+    if channels == 0 {
+        return Err("The source needs at least one channel".to_string());
+    }
+    Ok(SourceFormat { sample_rate, channels })
+}
+
+// Sleep until `frames` worth of audio has elapsed since `start`
+fn pace(start: Instant, frames: u64, sample_rate: u32) {
+    let target = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+    if let Some(wait) = target.checked_sub(start.elapsed()) {
+        thread::sleep(wait);
+    }
+}
+
+/// Convert a block of native device samples into -1.0..1.0 floats
+pub fn convert_samples<T>(data: &[T], out: &mut Vec<f32>)
+where
+    T: cpal::Sample,
+    f32: cpal::FromSample<T>,
+{
+    out.clear();
+    out.extend(data.iter().map(|&sample| sample.to_sample::<f32>()));
+}
+
+// Build an input stream for sample type `T`, converting every callback's data
+// to floats before handing it to the sink; `stop` is signalled once the sink
+// declines more samples. Stream errors are reported and counted in `errors`
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut sink: BlockSink,
+    stop: mpsc::Sender<()>,
+    errors: Arc<AtomicU64>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    // Sized up front so the callback does not allocate for any usual buffer size
+    let mut buffer = Vec::with_capacity(config.sample_rate.0 as usize * config.channels as usize);
+    let mut running = true;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            if !running {
+                return;
+            }
+            convert_samples(data, &mut buffer);
+            if !sink(&buffer) {
+                running = false;
+                let _ = stop.send(());
+            }
+        },
+        move |err| {
+            errors.fetch_add(1, Ordering::Relaxed);
+            eprintln!("Error during capture: {}", err);
+        },
+        None,
+    )
+}
+
+/// Live input from a cpal input device
+pub struct CpalSource {
+    device: cpal::Device,
+    config: cpal::StreamConfig,
+    sample_format: cpal::SampleFormat,
+    errors: Arc<AtomicU64>,
+}
+
+impl CpalSource {
+    /// Select the input device; `None` picks the host's or the system's default
+    pub fn open(host: Option<&str>, device: Option<&str>) -> Result<Self, String> {
+        let device = devices::select_input(host, device)?;
+        let config = device
+            .default_input_config()
+            .map_err(|err| format!("Error in input device configuration: {}", err))?;
+        Ok(Self {
+            device,
+            sample_format: config.sample_format(),
+            config: config.into(),
+            errors: Arc::new(AtomicU64::new(0)),
+        })
+    }
+}
+
+impl SampleSource for CpalSource {
+    fn name(&self) -> String {
+        let name = self.device.name().unwrap_or_else(|_| "unknown device".to_string());
+        format!("{} ({})", name, self.sample_format)
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat { sample_rate: self.config.sample_rate.0, channels: self.config.channels }
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn errors(&self) -> Option<Arc<AtomicU64>> {
+        Some(Arc::clone(&self.errors))
+    }
+
+    fn run(self: Box<Self>, sink: BlockSink) -> Result<(), String> {
+        let (device, config) = (&self.device, &self.config);
+        let (stop, stopped) = mpsc::channel();
+        let errors = Arc::clone(&self.errors);
+
+        let stream = match self.sample_format {
+            cpal::SampleFormat::I8 => build_stream::<i8>(device, config, sink, stop, errors),
+            cpal::SampleFormat::I16 => build_stream::<i16>(device, config, sink, stop, errors),
+            cpal::SampleFormat::I32 => build_stream::<i32>(device, config, sink, stop, errors),
+            cpal::SampleFormat::I64 => build_stream::<i64>(device, config, sink, stop, errors),
+            cpal::SampleFormat::U8 => build_stream::<u8>(device, config, sink, stop, errors),
+            cpal::SampleFormat::U16 => build_stream::<u16>(device, config, sink, stop, errors),
+            cpal::SampleFormat::U32 => build_stream::<u32>(device, config, sink, stop, errors),
+            cpal::SampleFormat::U64 => build_stream::<u64>(device, config, sink, stop, errors),
+            cpal::SampleFormat::F32 => build_stream::<f32>(device, config, sink, stop, errors),
+            cpal::SampleFormat::F64 => build_stream::<f64>(device, config, sink, stop, errors),
+            other => return Err(format!("Unsupported sample format: {}", other)),
+        }
+        .map_err(|err| format!("Failed to create input stream: {}", err))?;
+
+        stream.play().map_err(|err| format!("Failed to start the input stream: {}", err))?;
+
+        // Capture until the sink declines more samples
+        let _ = stopped.recv();
+        Ok(())
+    }
+}
+
+/// Samples read from a WAV file, optionally paced at the file's real-time rate
+pub struct FileSource {
+    path: PathBuf,
+    reader: WavReader,
+    block_frames: usize,
+    realtime: bool,
+}
+
+impl FileSource {
+    /// Open `path`, delivering blocks of `block_ms` as fast as the sink takes them
+    pub fn open(path: &Path, block_ms: u32) -> io::Result<Self> {
+        let reader = WavReader::open(path)?;
+        let block_frames = (reader.spec().sample_rate as u64 * block_ms as u64 / 1000).max(1) as usize;
+        Ok(Self { path: path.to_path_buf(), reader, block_frames, realtime: false })
+    }
+}
+
+impl SampleSource for FileSource {
+    fn name(&self) -> String {
+        let spec = self.reader.spec();
+        format!(
+            "{} ({}-bit {:?}, {:.3}s)",
+            self.path.display(),
+            spec.bits_per_sample,
+            spec.format,
+            self.reader.total_frames() as f64 / spec.sample_rate as f64
+        )
+    }
+
+    fn format(&self) -> SourceFormat {
+        let spec = self.reader.spec();
+        SourceFormat { sample_rate: spec.sample_rate, channels: spec.channels }
+    }
+
+    fn run(mut self: Box<Self>, mut sink: BlockSink) -> Result<(), String> {
+        let sample_rate = self.reader.spec().sample_rate;
+        let mut samples = Vec::new();
+        let mut frames_read: u64 = 0;
+        let start = Instant::now();
+
+        loop {
+            let frames = match self.reader.read_frames(self.block_frames, &mut samples) {
+                Ok(frames) => frames,
+                // Keep what a cut-off recording has rather than failing the whole run
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    eprintln!("Warning: {} ends early ({}), treating it as the end of input", self.path.display(), err);
+                    break;
+                }
+                Err(err) => return Err(format!("Unable to read WAV data from {}: {}", self.path.display(), err)),
+            };
+            if frames == 0 {
+                break;
+            }
+            if !sink(&samples) {
+                break;
+            }
+            frames_read += frames as u64;
+            if self.realtime {
+                pace(start, frames_read, sample_rate);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Raw interleaved PCM piped in on stdin, read until end of input
+pub struct StdinSource {
+    format: SourceFormat,
+    encoding: RawEncoding,
+    block_frames: usize,
+}
+
+impl StdinSource {
+    // Decode blocks from `input` until it ends, so tests can stand in for stdin
+    fn read_from(&self, mut input: impl Read, mut sink: BlockSink) -> Result<(), String> {
+        let width = match self.encoding {
+            RawEncoding::S16le => 2,
+            RawEncoding::F32le => 4,
+        };
+        let frame_bytes = width * self.format.channels as usize;
+        let mut bytes = vec![0u8; self.block_frames * frame_bytes];
+        let mut samples = Vec::with_capacity(self.block_frames * self.format.channels as usize);
+
+        loop {
+            // Fill a whole block unless the input ends first
+            let mut filled = 0;
+            while filled < bytes.len() {
+                match input.read(&mut bytes[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(format!("Unable to read from stdin: {}", e)),
+                }
+            }
+            let usable = filled - filled % frame_bytes;
+            if usable == 0 {
+                break;
+            }
+
+            samples.clear();
+            samples.extend(bytes[..usable].chunks_exact(width).map(|b| match self.encoding {
+                RawEncoding::S16le => i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.0,
+                RawEncoding::F32le => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            }));
+            if !sink(&samples) {
+                break;
+            }
+
+            if filled < bytes.len() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SampleSource for StdinSource {
+    fn name(&self) -> String {
+        format!("stdin ({:?})", self.encoding)
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format
+    }
+
+    fn reads_stdin(&self) -> bool {
+        true
+    }
+
+    fn run(self: Box<Self>, sink: BlockSink) -> Result<(), String> {
+        self.read_from(io::stdin().lock(), sink)
+    }
+}
+
+/// Synthetic test signal generated in real time
+pub struct Generator {
+    format: SourceFormat,
+    waveform: Waveform,
+    frequency: f32,
+    amplitude: f32,
+    duration: Option<Duration>,
+    block_frames: usize,
+    phase: f32,
+    noise_state: u32,
+}
+
+impl Generator {
+    // Fill `out` with `frames` interleaved frames of the configured waveform
+    fn fill(&mut self, frames: usize, out: &mut Vec<f32>) {
+        out.clear();
+        let step = 2.0 * PI * self.frequency / self.format.sample_rate as f32;
+        for _ in 0..frames {
+            let value = match self.waveform {
+                Waveform::Sine => {
+                    let value = self.amplitude * self.phase.sin();
+                    self.phase = (self.phase + step) % (2.0 * PI);
+                    value
+                }
+                Waveform::Noise => {
+                    // xorshift32, uniformly distributed in -1.0..1.0
+                    self.noise_state ^= self.noise_state << 13;
+                    self.noise_state ^= self.noise_state >> 17;
+                    self.noise_state ^= self.noise_state << 5;
+                    self.amplitude * (self.noise_state as f32 / u32::MAX as f32 * 2.0 - 1.0)
+                }
+                Waveform::Silence => 0.0,
+            };
+            out.extend(std::iter::repeat_n(value, self.format.channels as usize));
+        }
+    }
+}
+
+impl SampleSource for Generator {
+    fn name(&self) -> String {
+        format!("{:?} generator ({} Hz, amplitude {})", self.waveform, self.frequency, self.amplitude)
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format
+    }
+
+    fn run(mut self: Box<Self>, mut sink: BlockSink) -> Result<(), String> {
+        let total_frames = self
+            .duration
+            .map(|d| (d.as_secs_f64() * self.format.sample_rate as f64) as u64);
+        let mut samples = Vec::with_capacity(self.block_frames * self.format.channels as usize);
+        let mut frames_done: u64 = 0;
+        let start = Instant::now();
+
+        loop {
+            let frames = match total_frames {
+                Some(total) if frames_done >= total => break,
+                Some(total) => (total - frames_done).min(self.block_frames as u64) as usize,
+                None => self.block_frames,
+            };
+            self.fill(frames, &mut samples);
+            if !sink(&samples) {
+                break;
+            }
+            frames_done += frames as u64;
+            pace(start, frames_done, self.format.sample_rate);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Convert a single sample through the same path the cpal callback uses
+    fn convert<T>(sample: T) -> f32
+    where
+        T: cpal::Sample,
+        f32: cpal::FromSample<T>,
+    {
+        let mut out = Vec::new();
+        convert_samples(&[sample], &mut out);
+        out[0]
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} +/- {}, got {}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+
+    #[test]
+    fn converts_i16_extremes() {
+        assert_close(convert(i16::MIN), -1.0, 0.0);
+        assert_close(convert(i16::MAX), 1.0, 1.0 / 32_768.0);
+        assert_close(convert(0i16), 0.0, 0.0);
+    }
+
+    #[test]
+    fn converts_u16_extremes() {
+        assert_close(convert(u16::MIN), -1.0, 0.0);
+        assert_close(convert(u16::MAX), 1.0, 1.0 / 32_768.0);
+        assert_close(convert(32_768u16), 0.0, 0.0);
+    }
+
+    #[test]
+    fn converts_i32_extremes() {
+        assert_close(convert(i32::MIN), -1.0, 0.0);
+        assert_close(convert(i32::MAX), 1.0, 1e-6);
+        assert_close(convert(0i32), 0.0, 0.0);
+    }
+
+    #[test]
+    fn converts_u8_extremes() {
+        assert_close(convert(u8::MIN), -1.0, 0.0);
+        assert_close(convert(u8::MAX), 1.0, 1.0 / 128.0);
+        assert_close(convert(128u8), 0.0, 0.0);
+    }
+
+    #[test]
+    fn passes_f32_through() {
+        assert_close(convert(-1.0f32), -1.0, 0.0);
+        assert_close(convert(1.0f32), 1.0, 0.0);
+        assert_close(convert(0.25f32), 0.25, 0.0);
+    }
+
+    #[test]
+    fn reads_source_selections_from_json() {
+        let file: SourceConfig = serde_json::from_str(r#"{"type": "file", "path": "take.wav"}"#).unwrap();
+        assert!(matches!(file, SourceConfig::File { ref path, realtime: false } if path == Path::new("take.wav")));
+
+        let json = r#"{"type": "stdin", "sample_rate": 44100, "channels": 2, "encoding": "s16le"}"#;
+        let stdin: SourceConfig = serde_json::from_str(json).unwrap();
+        assert!(matches!(stdin, SourceConfig::Stdin { sample_rate: 44_100, channels: 2, encoding: RawEncoding::S16le }));
+
+        assert!(serde_json::from_str::<SourceConfig>(r#"{"type": "network"}"#).is_err());
+        assert!(matches!(SourceConfig::default(), SourceConfig::Device));
+    }
+
+    #[test]
+    fn converts_whole_blocks() {
+        let mut out = vec![9.0; 8];
+        convert_samples(&[i16::MIN, 0, 16_384], &mut out);
+        assert_eq!(out, vec![-1.0, 0.0, 0.5]);
+    }
+
+    // Run `source` to the end, collecting what it delivers
+    fn collect(source: Box<dyn SampleSource>) -> (Result<(), String>, Vec<f32>) {
+        let samples = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink_samples = Arc::clone(&samples);
+        let result = source.run(Box::new(move |block| {
+            sink_samples.lock().unwrap().extend_from_slice(block);
+            true
+        }));
+        let samples = samples.lock().unwrap().clone();
+        (result, samples)
+    }
+
+    #[test]
+    fn file_source_reads_back_written_wav() {
+        use crate::wav::{WavFormat, WavSpec, WavWriter};
+
+        let path = std::env::temp_dir().join(format!("db_meter_source_{}.wav", std::process::id()));
+        let spec = WavSpec { channels: 2, sample_rate: 8_000, bits_per_sample: 16, format: WavFormat::Pcm };
+        let written: Vec<f32> = (0..2 * 2_500).map(|n| ((n % 200) as f32 - 100.0) / 128.0).collect();
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        writer.write_samples(&written).unwrap();
+        writer.finalize().unwrap();
+
+        let source = FileSource::open(&path, 100).unwrap();
+        assert_eq!((source.format().sample_rate, source.format().channels), (8_000, 2));
+        let (result, read) = collect(Box::new(source));
+        result.unwrap();
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(&written) {
+            assert_close(*read, *written, 1.0 / 32_768.0);
+        }
+
+        // A recording cut off mid-frame ends early instead of failing
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(44 + 4 * 1_000 + 2).unwrap();
+        let (result, read) = collect(Box::new(FileSource::open(&path, 100).unwrap()));
+        result.unwrap();
+        assert_eq!(read.len(), 2 * 1_000);
+        assert_close(read[1_999], written[1_999], 1.0 / 32_768.0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn stdin_delivers_whole_frames_in_blocks() {
+        let source = StdinSource {
+            format: SourceFormat { sample_rate: 8_000, channels: 2 },
+            encoding: RawEncoding::S16le,
+            block_frames: 3,
+        };
+        // Seven frames and a stray byte from a cut-off frame
+        let mut input: Vec<u8> = (0..14i16).flat_map(|n| (n * 1_024).to_le_bytes()).collect();
+        input.push(0x7f);
+        let blocks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink_blocks = Arc::clone(&blocks);
+        source
+            .read_from(&input[..], Box::new(move |block| {
+                sink_blocks.lock().unwrap().push(block.to_vec());
+                true
+            }))
+            .unwrap();
+        let blocks = blocks.lock().unwrap();
+        assert_eq!(blocks.iter().map(Vec::len).collect::<Vec<_>>(), vec![6, 6, 2]);
+        assert_eq!(blocks[2], vec![12.0 * 1_024.0 / 32_768.0, 13.0 * 1_024.0 / 32_768.0]);
+    }
+
+    #[test]
+    fn generator_fills_every_channel() {
+        let config = SourceConfig::Generator {
+            waveform: Waveform::Sine,
+            frequency: 1_000.0,
+            amplitude: 0.5,
+            sample_rate: 8_000,
+            channels: 2,
+            duration_s: Some(0.05),
+        };
+        let (result, samples) = collect(config.open(20, None, None).unwrap());
+        result.unwrap();
+        assert_eq!(samples.len(), 2 * 400);
+        assert!(samples.chunks_exact(2).all(|frame| frame[0] == frame[1]));
+        let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert_close(peak, 0.5, 1e-3);
+    }
+
+    #[test]
+    fn rejects_empty_formats() {
+        let stdin = |sample_rate, channels| SourceConfig::Stdin { sample_rate, channels, encoding: RawEncoding::F32le };
+        assert!(stdin(48_000, 0).open(100, None, None).is_err());
+        assert!(stdin(0, 2).open(100, None, None).is_err());
+        assert!(stdin(48_000, 2).open(100, None, None).is_ok());
+        let generator = SourceConfig::Generator {
+            waveform: Waveform::Silence,
+            frequency: 1_000.0,
+            amplitude: 1.0,
+            sample_rate: 48_000,
+            channels: 0,
+            duration_s: None,
+        };
+        assert!(generator.open(100, None, None).is_err());
+    }
+}
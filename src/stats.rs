@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, VecDeque};
+
+// Histogram resolution in bins per dB
+const BINS_PER_DB: f32 = 10.0;
+
+/// Exceedance levels reported alongside Leq: LN is the level exceeded N% of the time
+pub const EXCEEDANCE_PERCENTS: [u32; 4] = [10, 50, 90, 95];
+
+/// Statistical noise metrics of a set of readings, in the unit of the readings
+#[derive(Debug, Clone, Copy)]
+pub struct StatisticsSummary {
+    /// Equivalent continuous level
+    pub leq: f32,
+    /// Lowest reading
+    pub lmin: f32,
+    /// Highest reading
+    pub lmax: f32,
+    /// Levels exceeded 10, 50, 90 and 95% of the time
+    pub exceedance: [f32; 4],
+    /// Time the readings cover
+    pub duration_s: f64,
+}
+
+/// Accumulates time-weighted dB readings into a level histogram, either over
+/// everything added or over a rolling window of the most recent seconds
+pub struct LevelStatistics {
+    histogram: BTreeMap<i32, f64>, // Seconds spent in each bin
+    energy: f64,                   // Sum of 10^(L/10) * duration
+    duration_s: f64,
+    min: f32,
+    max: f32,
+    window_s: Option<f64>,
+    entries: VecDeque<(f32, f64)>, // (reading, duration) inside the window
+}
+
+fn bin(db: f32) -> i32 {
+    (db * BINS_PER_DB).floor() as i32
+}
+
+fn bin_center(bin: i32) -> f32 {
+    (bin as f32 + 0.5) / BINS_PER_DB
+}
+
+// Statistics over the whole session
+impl Default for LevelStatistics {
+    fn default() -> Self {
+        Self {
+            histogram: BTreeMap::new(),
+            energy: 0.0,
+            duration_s: 0.0,
+            min: f32::MAX,
+            max: f32::MIN,
+            window_s: None,
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl LevelStatistics {
+    /// Statistics over the last `window_s` seconds only
+    pub fn rolling(window_s: f64) -> Self {
+        Self { window_s: Some(window_s), ..Self::default() }
+    }
+
+    /// Forget all readings, keeping the window length
+    pub fn reset(&mut self) {
+        *self = Self { window_s: self.window_s, ..Self::default() };
+    }
+
+    /// Window length, None for session statistics
+    pub fn window_s(&self) -> Option<f64> {
+        self.window_s
+    }
+
+    /// Add a reading that held for `duration_s` seconds; non-finite readings are ignored
+    pub fn add(&mut self, db: f32, duration_s: f64) {
+        if !db.is_finite() || duration_s <= 0.0 {
+            return;
+        }
+        *self.histogram.entry(bin(db)).or_insert(0.0) += duration_s;
+        self.energy += 10f64.powf(db as f64 / 10.0) * duration_s;
+        self.duration_s += duration_s;
+        self.min = self.min.min(db);
+        self.max = self.max.max(db);
+
+        if let Some(window_s) = self.window_s {
+            self.entries.push_back((db, duration_s));
+            // Small tolerance so rounding in the running duration does not keep an extra reading
+            while self.duration_s - self.entries.front().map_or(0.0, |entry| entry.1) >= window_s - 1e-9 {
+                let (old_db, old_duration) = self.entries.pop_front().unwrap();
+                self.remove(old_db, old_duration);
+            }
+        }
+    }
+
+    fn remove(&mut self, db: f32, duration_s: f64) {
+        let key = bin(db);
+        if let Some(time) = self.histogram.get_mut(&key) {
+            *time -= duration_s;
+            if *time <= 1e-9 {
+                self.histogram.remove(&key);
+            }
+        }
+        self.energy = (self.energy - 10f64.powf(db as f64 / 10.0) * duration_s).max(0.0);
+        self.duration_s -= duration_s;
+    }
+
+    // Level exceeded `percent`% of the time, at the histogram's resolution
+    fn exceeded(&self, percent: u32) -> f32 {
+        let target = self.duration_s * percent as f64 / 100.0;
+        let mut accumulated = 0.0;
+        for (&key, &time) in self.histogram.iter().rev() {
+            accumulated += time;
+            if accumulated >= target {
+                return bin_center(key);
+            }
+        }
+        self.histogram.keys().next().map_or(f32::NEG_INFINITY, |&key| bin_center(key))
+    }
+
+    /// None until a reading has been added
+    pub fn summary(&self) -> Option<StatisticsSummary> {
+        if self.histogram.is_empty() || self.duration_s <= 0.0 {
+            return None;
+        }
+        let (lmin, lmax) = if self.window_s.is_some() {
+            self.entries
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), &(db, _)| (min.min(db), max.max(db)))
+        } else {
+            (self.min, self.max)
+        };
+        Some(StatisticsSummary {
+            leq: (10.0 * (self.energy / self.duration_s).log10()) as f32,
+            lmin,
+            lmax,
+            exceedance: EXCEEDANCE_PERCENTS.map(|percent| self.exceeded(percent)),
+            duration_s: self.duration_s,
+        })
+    }
+
+    /// Share of time spent in bins `width_db` wide, as (lower edge, fraction) from
+    /// the quietest bin to the loudest
+    pub fn histogram(&self, width_db: f32) -> Vec<(f32, f64)> {
+        let mut bins: BTreeMap<i64, f64> = BTreeMap::new();
+        for (&key, &time) in &self.histogram {
+            let db = key as f32 / BINS_PER_DB;
+            *bins.entry((db / width_db).floor() as i64).or_insert(0.0) += time;
+        }
+        let (first, last) = match (bins.keys().next(), bins.keys().next_back()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return Vec::new(),
+        };
+        (first..=last)
+            .map(|key| (key as f32 * width_db, bins.get(&key).copied().unwrap_or(0.0) / self.duration_s))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() <= tolerance, "expected {} +/- {}, got {}", expected, tolerance, actual);
+    }
+
+    #[test]
+    fn leq_of_constant_level() {
+        let mut stats = LevelStatistics::default();
+        for _ in 0..100 {
+            stats.add(60.0, 0.1);
+        }
+        let summary = stats.summary().unwrap();
+        assert_close(summary.leq, 60.0, 1e-3);
+        assert_close(summary.lmin, 60.0, 0.0);
+        assert_close(summary.lmax, 60.0, 0.0);
+        for level in summary.exceedance {
+            assert_close(level, 60.0, 0.1);
+        }
+    }
+
+    #[test]
+    fn leq_is_an_energy_average() {
+        // Half the time at 70 dB and half at 60 dB: 10 * log10((10^7 + 10^6) / 2)
+        let mut stats = LevelStatistics::default();
+        stats.add(70.0, 1.0);
+        stats.add(60.0, 1.0);
+        assert_close(stats.summary().unwrap().leq, 67.404, 1e-2);
+    }
+
+    #[test]
+    fn exceedance_levels_of_a_uniform_sweep() {
+        // One second at each level from 0 to 99 dB
+        let mut stats = LevelStatistics::default();
+        for db in 0..100 {
+            stats.add(db as f32, 1.0);
+        }
+        let [l10, l50, l90, l95] = stats.summary().unwrap().exceedance;
+        assert_close(l10, 90.0, 0.1);
+        assert_close(l50, 50.0, 0.1);
+        assert_close(l90, 10.0, 0.1);
+        assert_close(l95, 5.0, 0.1);
+    }
+
+    #[test]
+    fn rolling_window_forgets_old_readings() {
+        let mut stats = LevelStatistics::rolling(10.0);
+        for _ in 0..100 {
+            stats.add(90.0, 0.1);
+        }
+        for _ in 0..100 {
+            stats.add(40.0, 0.1);
+        }
+        let summary = stats.summary().unwrap();
+        assert_close(summary.leq, 40.0, 1e-3);
+        assert_close(summary.lmax, 40.0, 0.0);
+        assert!((summary.duration_s - 10.0).abs() < 0.2, "{}", summary.duration_s);
+    }
+
+    #[test]
+    fn ignores_silence_and_histogram_sums_to_one() {
+        let mut stats = LevelStatistics::default();
+        assert!(stats.summary().is_none());
+        stats.add(f32::NEG_INFINITY, 1.0);
+        assert!(stats.summary().is_none());
+
+        stats.add(41.0, 1.0);
+        stats.add(55.0, 3.0);
+        let histogram = stats.histogram(5.0);
+        assert_eq!(histogram.first().unwrap().0, 40.0);
+        assert_eq!(histogram.last().unwrap().0, 55.0);
+        assert_close(histogram.iter().map(|bin| bin.1).sum::<f64>() as f32, 1.0, 1e-6);
+    }
+}
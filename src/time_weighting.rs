@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+/// IEC 61672-1 exponential time weightings
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeWeighting {
+    /// Plain RMS of each block
+    None,
+    /// Fast, 125 ms time constant
+    #[default]
+    Fast,
+    /// Slow, 1 s time constant
+    Slow,
+    /// Impulse, 35 ms rise and 1.5 s decay
+    Impulse,
+}
+
+impl TimeWeighting {
+    // Time constant of the exponential average in seconds and, for Impulse, the
+    // time constant its peaks decay with
+    fn time_constants(self) -> Option<(f64, Option<f64>)> {
+        match self {
+            TimeWeighting::None => None,
+            TimeWeighting::Fast => Some((0.125, None)),
+            TimeWeighting::Slow => Some((1.0, None)),
+            TimeWeighting::Impulse => Some((0.035, Some(1.5))),
+        }
+    }
+}
+
+/// Exponentially time-weighted mean square of a signal, updated every sample so
+/// the response does not depend on the driver's buffer size. Impulse follows the
+/// 35 ms average with a peak detector, so steady signals read the same as Fast
+#[derive(Debug, Clone)]
+pub struct TimeWeightedLevel {
+    averaging: f64,
+    decay: Option<f64>,
+    mean_square: f64,
+    held: f64, // Peak of the mean square, decaying, for Impulse
+}
+
+impl TimeWeightedLevel {
+    /// None for `TimeWeighting::None`, where each block is measured on its own
+    pub fn new(weighting: TimeWeighting, sample_rate: u32) -> Option<Self> {
+        let (averaging, decay) = weighting.time_constants()?;
+        let coefficient = |tau: f64| 1.0 - (-1.0 / (tau * sample_rate as f64)).exp();
+        Some(Self {
+            averaging: coefficient(averaging),
+            decay: decay.map(coefficient),
+            mean_square: 0.0,
+            held: 0.0,
+        })
+    }
+
+    /// Feed a block of samples and return the weighted RMS at the end of it
+    pub fn process(&mut self, samples: &[f32]) -> f32 {
+        for &sample in samples {
+            let square = sample as f64 * sample as f64;
+            self.mean_square += self.averaging * (square - self.mean_square);
+            if let Some(decay) = self.decay {
+                self.held = (self.held * (1.0 - decay)).max(self.mean_square);
+            }
+        }
+        match self.decay {
+            Some(_) => self.held.sqrt() as f32,
+            None => self.mean_square.sqrt() as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn level_db(level: f32) -> f32 {
+        20.0 * level.log10()
+    }
+
+    #[test]
+    fn fast_reaches_steady_state_of_a_sine() {
+        let mut meter = TimeWeightedLevel::new(TimeWeighting::Fast, SAMPLE_RATE).unwrap();
+        let sine: Vec<f32> = (0..SAMPLE_RATE)
+            .map(|n| (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect();
+        let level = meter.process(&sine);
+        assert!((level_db(level) + 3.01).abs() < 0.1, "got {} dB", level_db(level));
+    }
+
+    #[test]
+    fn steady_sine_reads_the_same_in_every_weighting() {
+        let sine: Vec<f32> = (0..SAMPLE_RATE * 10)
+            .map(|n| (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect();
+        for weighting in [TimeWeighting::Fast, TimeWeighting::Slow, TimeWeighting::Impulse] {
+            let mut meter = TimeWeightedLevel::new(weighting, SAMPLE_RATE).unwrap();
+            let level = level_db(meter.process(&sine));
+            assert!((level + 3.01).abs() < 0.1, "{:?}: got {} dB", weighting, level);
+        }
+    }
+
+    #[test]
+    fn decay_rates_match_time_constants() {
+        // An exponential with time constant tau decays by 10 * log10(e) / tau dB per second
+        for (weighting, tau) in [(TimeWeighting::Fast, 0.125), (TimeWeighting::Slow, 1.0), (TimeWeighting::Impulse, 1.5)] {
+            let mut meter = TimeWeightedLevel::new(weighting, SAMPLE_RATE).unwrap();
+            meter.process(&vec![1.0; SAMPLE_RATE as usize * 10]);
+            let start = level_db(meter.process(&[]));
+            let end = level_db(meter.process(&vec![0.0; SAMPLE_RATE as usize / 10]));
+            let expected = 0.1 * 10.0 * std::f32::consts::LOG10_E / tau;
+            assert!((start - end - expected).abs() < 0.05, "{:?}: decayed {} dB", weighting, start - end);
+        }
+    }
+
+    #[test]
+    fn result_is_independent_of_block_size() {
+        let signal: Vec<f32> = (0..4800).map(|n| ((n * 7919) % 1000) as f32 / 1000.0 - 0.5).collect();
+        let mut whole = TimeWeightedLevel::new(TimeWeighting::Impulse, SAMPLE_RATE).unwrap();
+        let mut split = TimeWeightedLevel::new(TimeWeighting::Impulse, SAMPLE_RATE).unwrap();
+        let expected = whole.process(&signal);
+        let actual = signal.chunks(37).map(|block| split.process(block)).last().unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn none_has_no_averager() {
+        assert!(TimeWeightedLevel::new(TimeWeighting::None, SAMPLE_RATE).is_none());
+    }
+}
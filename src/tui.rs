@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::scale::MeterScale;
+
+// Readings kept for the history graph, one per block
+const HISTORY_LENGTH: usize = 2000;
+// Width of the history graph's axis labels
+const AXIS_WIDTH: usize = 7;
+const EIGHTHS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Keys understood by the dashboard
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Quit,
+    Pause,
+    Reset,
+}
+
+// Full-screen terminal session: alternate screen, hidden cursor, no line
+// wrapping and, when stdin is a terminal, unbuffered key input without echo.
+// Everything is restored on drop
+pub struct Terminal {
+    original: Option<libc::termios>,
+}
+
+impl Terminal {
+    pub fn enter(raw_input: bool) -> Self {
+        let original = if raw_input { enable_raw_mode() } else { None };
+        print!("\x1b[?1049h\x1b[?25l\x1b[?7l");
+        std::io::stdout().flush().unwrap();
+        Self { original }
+    }
+
+    // Terminal size as (columns, rows), 80x24 when it cannot be queried
+    pub fn size() -> (usize, usize) {
+        // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass
+        unsafe {
+            let mut size: libc::winsize = std::mem::zeroed();
+            if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 && size.ws_row > 0 {
+                return (size.ws_col as usize, size.ws_row as usize);
+            }
+        }
+        (80, 24)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?7h\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        if let Some(original) = &self.original {
+            // SAFETY: restores the attributes read by `enable_raw_mode`
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+            }
+        }
+    }
+}
+
+// Switch stdin to non-canonical mode without echo or signal keys, returning the
+// previous attributes; None when stdin is not a terminal
+fn enable_raw_mode() -> Option<libc::termios> {
+    // SAFETY: plain termios calls on stdin with structs owned by this function
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) == 0 {
+            return None;
+        }
+        let mut original: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+            return None;
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+            return None;
+        }
+        Some(original)
+    }
+}
+
+// Read keys from stdin on a background thread
+pub fn spawn_key_reader() -> mpsc::Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut byte = [0u8; 1];
+        while let Ok(1) = stdin.read(&mut byte) {
+            let key = match byte[0] {
+                b'q' | b'Q' | 3 => Key::Quit, // 3 is Ctrl-C, which raw mode delivers as a byte
+                b'p' | b'P' | b' ' => Key::Pause,
+                b'r' | b'R' => Key::Reset,
+                _ => continue,
+            };
+            if sender.send(key).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// Color for a 0.0-1.0 deflection, matching the single-line meter
+pub fn color(fraction: f32) -> &'static str {
+    if fraction < 0.33 {
+        "32"
+    } else if fraction < 0.66 {
+        "33"
+    } else {
+        "31"
+    }
+}
+
+// Screen state that is not part of the measurement: history and pause
+#[derive(Default)]
+pub struct Dashboard {
+    pub paused: bool,
+    history: VecDeque<(f32, bool)>, // (deflection, alert) per block
+}
+
+impl Dashboard {
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn push_history(&mut self, fraction: f32, alert: bool) {
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back((fraction, alert));
+    }
+
+    // Colored bar of `width` cells with a peak-hold marker
+    pub fn bar(width: usize, fraction: f32, hold: f32) -> String {
+        let filled = ((fraction * width as f32).round().max(0.0) as usize).min(width);
+        let marker = ((hold * width as f32).round() as usize).clamp(1, width) - 1;
+        let cells: String = (0..width)
+            .map(|cell| {
+                if cell == marker && hold > 0.0 {
+                    '|'
+                } else if cell < filled {
+                    '#'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        format!("\x1b[{}m[{}]\x1b[0m", color(fraction), cells)
+    }
+
+    // Scrolling graph of the last `width` readings, newest on the right, with
+    // the scale's ticks as y-axis labels
+    pub fn graph(&self, scale: &MeterScale, width: usize, height: usize) -> Vec<String> {
+        let columns = width.saturating_sub(AXIS_WIDTH);
+        let mut labels = vec![String::new(); height];
+        for (db, label) in scale.ticks() {
+            let row = height - 1 - ((scale.fraction(db) * (height - 1) as f32).round() as usize).min(height - 1);
+            labels[row] = label;
+        }
+
+        let skip = self.history.len().saturating_sub(columns);
+        let visible: Vec<(f32, bool)> = self.history.iter().skip(skip).copied().collect();
+        let padding = columns - visible.len();
+
+        (0..height)
+            .map(|row| {
+                let mut line = format!("{:>width$}┤", labels[row], width = AXIS_WIDTH - 1);
+                line.push_str(&" ".repeat(padding));
+                let floor = (height - 1 - row) as f32;
+                for &(fraction, alert) in &visible {
+                    let fill = (fraction * height as f32 - floor).clamp(0.0, 1.0);
+                    let cell = EIGHTHS[(fill * 8.0).round() as usize];
+                    let color = if alert { "31" } else { color(fraction) };
+                    line.push_str(&format!("\x1b[{}m{}", color, cell));
+                }
+                line.push_str("\x1b[0m");
+                line
+            })
+            .collect()
+    }
+
+    // Draw `lines` from the top of the screen, clearing whatever was below
+    pub fn draw(lines: &[String]) {
+        let mut output = String::from("\x1b[H");
+        for (index, line) in lines.iter().enumerate() {
+            if index > 0 {
+                output.push_str("\r\n");
+            }
+            output.push_str(line);
+            output.push_str("\x1b[K");
+        }
+        output.push_str("\x1b[J");
+        print!("{}", output);
+        std::io::stdout().flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scale::DisplayScale;
+
+    fn strip_colors(text: &str) -> String {
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for ch in text.chars() {
+            match (in_escape, ch) {
+                (false, '\x1b') => in_escape = true,
+                (true, 'm') => in_escape = false,
+                (true, _) => {}
+                (false, ch) => plain.push(ch),
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn bar_shows_fill_and_hold_marker() {
+        let bar = strip_colors(&Dashboard::bar(10, 0.3, 0.8));
+        assert_eq!(bar, "[###    |  ]");
+    }
+
+    #[test]
+    fn graph_puts_newest_reading_on_the_right() {
+        let mut dashboard = Dashboard::default();
+        dashboard.push_history(1.0, false);
+        dashboard.push_history(0.0, false);
+        dashboard.push_history(0.5, false);
+        let scale = MeterScale::new(DisplayScale::Linear, -100.0, 0.0);
+        let rows: Vec<String> = dashboard.graph(&scale, AXIS_WIDTH + 5, 4).iter().map(|row| strip_colors(row)).collect();
+        assert_eq!(rows.len(), 4);
+        assert!(rows.iter().all(|row| row.chars().count() == AXIS_WIDTH + 5), "{:?}", rows);
+        let bottom: Vec<char> = rows[3].chars().skip(AXIS_WIDTH).collect();
+        assert_eq!(bottom, vec![' ', ' ', '█', ' ', '█']);
+        let top: Vec<char> = rows[0].chars().skip(AXIS_WIDTH).collect();
+        assert_eq!(top, vec![' ', ' ', '█', ' ', ' ']);
+    }
+}
@@ -0,0 +1,338 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Sample encoding stored in the fmt chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WavFormat {
+    /// Integer PCM
+    Pcm,
+    /// IEEE float
+    Float,
+}
+
+/// Layout and encoding of a WAV file
+#[derive(Debug, Clone, Copy)]
+pub struct WavSpec {
+    /// Samples per frame
+    pub channels: u16,
+    /// Frames per second
+    pub sample_rate: u32,
+    /// Bits in each sample
+    pub bits_per_sample: u16,
+    /// Integer or float samples
+    pub format: WavFormat,
+}
+
+/// Minimal RIFF/WAVE reader for integer PCM and IEEE float files
+pub struct WavReader {
+    reader: BufReader<File>,
+    spec: WavSpec,
+    data_len: u64,
+    remaining: u64,
+    truncated: bool, // The data chunk ended early
+    bytes: Vec<u8>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl WavReader {
+    /// Open `path` and parse its headers, leaving it positioned at the sample data
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut riff = [0u8; 12];
+        reader.read_exact(&mut riff)?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(invalid("not a RIFF/WAVE file"));
+        }
+
+        let mut spec = None;
+        loop {
+            let mut header = [0u8; 8];
+            reader.read_exact(&mut header)?;
+            let id = &header[0..4];
+            let size = read_u32(&header[4..8]) as u64;
+
+            if id == b"fmt " {
+                let mut chunk = vec![0u8; size as usize];
+                reader.read_exact(&mut chunk)?;
+                if chunk.len() < 16 {
+                    return Err(invalid("fmt chunk too short"));
+                }
+                let mut tag = read_u16(&chunk[0..2]);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real format in the sub-format GUID
+                if tag == 0xFFFE && chunk.len() >= 26 {
+                    tag = read_u16(&chunk[24..26]);
+                }
+                let format = match tag {
+                    1 => WavFormat::Pcm,
+                    3 => WavFormat::Float,
+                    _ => return Err(invalid("unsupported WAV encoding (only PCM and float)")),
+                };
+                let bits_per_sample = read_u16(&chunk[14..16]);
+                let supported = match format {
+                    WavFormat::Pcm => matches!(bits_per_sample, 8 | 16 | 24 | 32),
+                    WavFormat::Float => matches!(bits_per_sample, 32 | 64),
+                };
+                if !supported {
+                    return Err(invalid("unsupported bits per sample"));
+                }
+                spec = Some(WavSpec {
+                    channels: read_u16(&chunk[2..4]),
+                    sample_rate: read_u32(&chunk[4..8]),
+                    bits_per_sample,
+                    format,
+                });
+            } else if id == b"data" {
+                let spec = spec.ok_or_else(|| invalid("data chunk before fmt chunk"))?;
+                if spec.channels == 0 {
+                    return Err(invalid("WAV file has no channels"));
+                }
+                if spec.sample_rate == 0 {
+                    return Err(invalid("WAV file has a sample rate of 0"));
+                }
+                return Ok(Self {
+                    reader,
+                    spec,
+                    data_len: size,
+                    remaining: size,
+                    truncated: false,
+                    bytes: Vec::new(),
+                });
+            } else {
+                // Chunks are word aligned
+                reader.seek(SeekFrom::Current((size + (size & 1)) as i64))?;
+            }
+        }
+    }
+
+    /// Layout and encoding of the file
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    fn frame_bytes(&self) -> usize {
+        self.spec.channels as usize * (self.spec.bits_per_sample as usize / 8)
+    }
+
+    /// Length of the sample data in frames
+    pub fn total_frames(&self) -> u64 {
+        self.data_len / self.frame_bytes() as u64
+    }
+
+    /// Read up to `frames` interleaved frames into `out` as samples in -1.0..1.0,
+    /// returning the number of frames read (0 at the end of the data chunk). A file
+    /// cut short delivers the whole frames it has, then fails with `UnexpectedEof`
+    pub fn read_frames(&mut self, frames: usize, out: &mut Vec<f32>) -> io::Result<usize> {
+        out.clear();
+        let cut_short = || io::Error::new(io::ErrorKind::UnexpectedEof, "WAV data ends before its declared length");
+        if self.truncated {
+            return Err(cut_short());
+        }
+        let frame_bytes = self.frame_bytes();
+        let available = (self.remaining / frame_bytes as u64) as usize;
+        let frames = frames.min(available);
+        if frames == 0 {
+            return Ok(0);
+        }
+
+        self.bytes.resize(frames * frame_bytes, 0);
+        let mut filled = 0;
+        while filled < self.bytes.len() {
+            match self.reader.read(&mut self.bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        if filled < self.bytes.len() {
+            self.truncated = true;
+            self.bytes.truncate(filled - filled % frame_bytes);
+            if self.bytes.is_empty() {
+                return Err(cut_short());
+            }
+        }
+        let frames = self.bytes.len() / frame_bytes;
+        self.remaining -= self.bytes.len() as u64;
+
+        let width = self.spec.bits_per_sample as usize / 8;
+        out.extend(self.bytes.chunks_exact(width).map(|b| match (self.spec.format, width) {
+            (WavFormat::Pcm, 1) => (b[0] as f32 - 128.0) / 128.0,
+            (WavFormat::Pcm, 2) => i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.0,
+            (WavFormat::Pcm, 3) => {
+                (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0
+            }
+            (WavFormat::Pcm, _) => {
+                i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+            }
+            (WavFormat::Float, 4) => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            (WavFormat::Float, _) => {
+                f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+            }
+        }));
+
+        Ok(frames)
+    }
+}
+
+// Size of the RIFF, fmt and data headers written by `WavWriter`
+const HEADER_LEN: u64 = 44;
+
+// Largest data chunk whose RIFF size still fits the 32-bit field
+const MAX_DATA_LEN: u64 = u32::MAX as u64 - (HEADER_LEN - 8);
+
+/// RIFF/WAVE writer for 16/24/32-bit PCM and 32-bit float. The chunk sizes are
+/// patched in by `flush` and `finalize`
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    spec: WavSpec,
+    data_len: u64,
+}
+
+impl WavWriter {
+    /// Create `path`, writing a header that `flush` and `finalize` complete
+    pub fn create(path: &Path, spec: WavSpec) -> io::Result<Self> {
+        let supported = match spec.format {
+            WavFormat::Pcm => matches!(spec.bits_per_sample, 16 | 24 | 32),
+            WavFormat::Float => spec.bits_per_sample == 32,
+        };
+        if !supported || spec.channels == 0 {
+            return Err(invalid("unsupported WAV format for writing"));
+        }
+        let mut writer = Self { writer: BufWriter::new(File::create(path)?), spec, data_len: 0 };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    /// Most frames a file of `spec` can hold before its chunk sizes overflow,
+    /// a little over 4 GiB of sample data
+    pub fn max_frames(spec: WavSpec) -> u64 {
+        MAX_DATA_LEN / (spec.channels as u64 * (spec.bits_per_sample as u64 / 8)).max(1)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let spec = self.spec;
+        let block_align = spec.channels * (spec.bits_per_sample / 8);
+        let tag: u16 = match spec.format {
+            WavFormat::Pcm => 1,
+            WavFormat::Float => 3,
+        };
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&((HEADER_LEN - 8 + self.data_len) as u32).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&tag.to_le_bytes());
+        header.extend_from_slice(&spec.channels.to_le_bytes());
+        header.extend_from_slice(&spec.sample_rate.to_le_bytes());
+        header.extend_from_slice(&(spec.sample_rate * block_align as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&spec.bits_per_sample.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&(self.data_len as u32).to_le_bytes());
+        self.writer.write_all(&header)
+    }
+
+    /// Append interleaved samples in -1.0..1.0; PCM output is clipped to full scale.
+    /// Fails without writing anything once the file would pass the RIFF size limit
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let len = (samples.len() * (self.spec.bits_per_sample as usize / 8)) as u64;
+        if self.data_len + len > MAX_DATA_LEN {
+            return Err(io::Error::other("WAV file would exceed the 4 GiB RIFF limit"));
+        }
+        for &sample in samples {
+            let clipped = sample.clamp(-1.0, 1.0);
+            match (self.spec.format, self.spec.bits_per_sample) {
+                (WavFormat::Pcm, 16) => {
+                    self.writer.write_all(&((clipped * 32_767.0).round() as i16).to_le_bytes())?
+                }
+                (WavFormat::Pcm, 24) => {
+                    let value = (clipped * 8_388_607.0).round() as i32;
+                    self.writer.write_all(&value.to_le_bytes()[..3])?
+                }
+                (WavFormat::Pcm, _) => {
+                    self.writer.write_all(&((clipped as f64 * 2_147_483_647.0).round() as i32).to_le_bytes())?
+                }
+                (WavFormat::Float, _) => self.writer.write_all(&sample.to_le_bytes())?,
+            }
+        }
+        self.data_len += len;
+        Ok(())
+    }
+
+    /// Size of the file written so far, headers included
+    pub fn bytes_written(&self) -> u64 {
+        HEADER_LEN + self.data_len
+    }
+
+    /// Bring the chunk sizes up to date and push everything to disk, so the file
+    /// stays readable if the process dies before `finalize`
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+
+    /// Write the final chunk sizes and flush the file
+    pub fn finalize(mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_files_read_back() {
+        let path = std::env::temp_dir().join(format!("db_meter_wav_{}.wav", std::process::id()));
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0, 0.25];
+        for (format, bits, tolerance) in [(WavFormat::Pcm, 16, 1e-4), (WavFormat::Pcm, 24, 1e-6), (WavFormat::Float, 32, 0.0)] {
+            let spec = WavSpec { channels: 2, sample_rate: 48_000, bits_per_sample: bits, format };
+            let mut writer = WavWriter::create(&path, spec).unwrap();
+            writer.write_samples(&samples[..2]).unwrap();
+            writer.flush().unwrap();
+            writer.write_samples(&samples[2..]).unwrap();
+            assert_eq!(writer.bytes_written(), HEADER_LEN + 6 * bits as u64 / 8);
+            writer.finalize().unwrap();
+
+            let mut reader = WavReader::open(&path).unwrap();
+            assert_eq!(reader.spec().channels, 2);
+            assert_eq!(reader.total_frames(), 3);
+            let mut out = Vec::new();
+            assert_eq!(reader.read_frames(10, &mut out).unwrap(), 3);
+            for (read, written) in out.iter().zip(samples) {
+                assert!((read - written).abs() <= tolerance, "{:?} {}: {} vs {}", format, bits, read, written);
+            }
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_pass_the_riff_limit() {
+        let path = std::env::temp_dir().join(format!("db_meter_wav_limit_{}.wav", std::process::id()));
+        let spec = WavSpec { channels: 2, sample_rate: 48_000, bits_per_sample: 16, format: WavFormat::Pcm };
+        assert_eq!(WavWriter::max_frames(spec), (u32::MAX as u64 - 36) / 4);
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        // Pretend the file is full rather than writing 4 GiB
+        writer.data_len = WavWriter::max_frames(spec) * 4 - 4;
+        writer.write_samples(&[0.0, 0.0]).unwrap();
+        assert!(writer.write_samples(&[0.0, 0.0]).is_err());
+        assert_eq!(writer.bytes_written(), HEADER_LEN + WavWriter::max_frames(spec) * 4);
+        drop(writer);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::f64::consts::PI;
+use serde::{Deserialize, Serialize};
+
+use crate::filter::Biquad;
+
+// IEC 61672-1 pole frequencies in Hz
+const F1: f64 = 20.598997;
+const F2: f64 = 107.65265;
+const F3: f64 = 737.86223;
+const F4: f64 = 12194.217;
+
+/// Frequency weighting applied before the RMS calculation
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub enum Weighting {
+    /// A-weighting, following the ear at moderate levels
+    A,
+    /// C-weighting, nearly flat, for high levels and peaks
+    C,
+    /// Flat, no weighting
+    #[default]
+    Z,
+}
+
+impl Weighting {
+    /// Unit suffix, e.g. "(A)", empty for Z-weighting
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Weighting::A => "(A)",
+            Weighting::C => "(C)",
+            Weighting::Z => "",
+        }
+    }
+}
+
+// Bilinear transform of N(s) / ((s + a)(s + b)), where the numerator is s^2
+// (high-pass pair) or 1 (low-pass pair)
+fn section(sample_rate: f64, high_pass: bool, a: f64, b: f64) -> Biquad {
+    let k = 2.0 * sample_rate;
+    let numerator = if high_pass {
+        [k * k, -2.0 * k * k, k * k]
+    } else {
+        [1.0, 2.0, 1.0]
+    };
+    let denominator = [
+        (k + a) * (k + b),
+        (k + a) * (b - k) + (a - k) * (k + b),
+        (a - k) * (b - k),
+    ];
+    Biquad::new(numerator, denominator)
+}
+
+/// A- or C-weighting filter for one channel, normalized to 0 dB at 1 kHz
+#[derive(Debug, Clone)]
+pub struct WeightingFilter {
+    sections: Vec<Biquad>,
+    gain: f64,
+}
+
+impl WeightingFilter {
+    /// None for Z-weighting, which leaves the signal untouched
+    pub fn new(weighting: Weighting, sample_rate: u32) -> Option<Self> {
+        let fs = sample_rate as f64;
+        let w = |f: f64| 2.0 * PI * f;
+        let sections = match weighting {
+            Weighting::A => vec![
+                section(fs, true, w(F1), w(F1)),
+                section(fs, true, w(F2), w(F3)),
+                section(fs, false, w(F4), w(F4)),
+            ],
+            Weighting::C => vec![
+                section(fs, true, w(F1), w(F1)),
+                section(fs, false, w(F4), w(F4)),
+            ],
+            Weighting::Z => return None,
+        };
+
+        let mut filter = Self { sections, gain: 1.0 };
+        filter.gain = 1.0 / filter.magnitude(1000.0, fs);
+        Some(filter)
+    }
+
+    // Magnitude response of the digital cascade at `frequency`
+    fn magnitude(&self, frequency: f64, sample_rate: f64) -> f64 {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        self.sections
+            .iter()
+            .map(|section| section.magnitude(omega))
+            .product::<f64>()
+            * self.gain
+    }
+
+    /// Write the weighted version of `input` into `output`
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        output.clear();
+        output.extend(input.iter().map(|&sample| {
+            let weighted = self
+                .sections
+                .iter_mut()
+                .fold(sample as f64, |value, section| section.process(value));
+            (weighted * self.gain) as f32
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const FREQUENCIES: [f64; 10] = [31.5, 63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+
+    // Level change in dB of a sine at `frequency` after it settles in the filter
+    fn measured_response(weighting: Weighting, frequency: f64) -> f64 {
+        let mut filter = WeightingFilter::new(weighting, SAMPLE_RATE).unwrap();
+        let input: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|n| (2.0 * PI * frequency * n as f64 / SAMPLE_RATE as f64).sin() as f32)
+            .collect();
+        let mut output = Vec::new();
+        filter.process(&input, &mut output);
+
+        let settled = SAMPLE_RATE as usize / 2;
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / samples.len() as f64).sqrt()
+        };
+        20.0 * (rms(&output[settled..]) / rms(&input[settled..])).log10()
+    }
+
+    // Nominal IEC 61672-1 response; up to 4 kHz the bilinear design stays within
+    // 0.2 dB, above that the class 1 tolerance limits apply
+    fn check(weighting: Weighting, nominal: [f64; 10]) {
+        for (&frequency, &expected) in FREQUENCIES.iter().zip(&nominal) {
+            let actual = measured_response(weighting, frequency);
+            let (below, above) = match frequency as u32 {
+                8000 => (2.5, 1.5),
+                16000 => (17.0, 3.5),
+                _ => (0.2, 0.2),
+            };
+            assert!(
+                actual >= expected - below && actual <= expected + above,
+                "{:?} at {} Hz: expected {} dB, got {:.2} dB",
+                weighting,
+                frequency,
+                expected,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn a_weighting_response() {
+        check(Weighting::A, [-39.4, -26.2, -16.1, -8.6, -3.2, 0.0, 1.2, 1.0, -1.1, -6.6]);
+    }
+
+    #[test]
+    fn c_weighting_response() {
+        check(Weighting::C, [-3.0, -0.8, -0.2, 0.0, 0.0, 0.0, -0.2, -0.8, -3.0, -8.5]);
+    }
+
+    #[test]
+    fn normalized_at_1_khz_for_other_rates() {
+        for sample_rate in [44_100, 96_000] {
+            let filter = WeightingFilter::new(Weighting::A, sample_rate).unwrap();
+            assert!((filter.magnitude(1000.0, sample_rate as f64) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn z_weighting_has_no_filter() {
+        assert!(WeightingFilter::new(Weighting::Z, SAMPLE_RATE).is_none());
+    }
+}